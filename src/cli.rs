@@ -2,8 +2,19 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
+use crate::{file, history, key_binds};
+
 #[derive(Parser)]
 #[command(version, about)]
 pub struct Cli {
     pub path: Option<PathBuf>,
+    /// Undo steps kept per file
+    #[arg(long, default_value_t = history::DEFAULT_LIMIT)]
+    pub undo_limit: usize,
+    /// Copy kept of a file before it is saved over
+    #[arg(long, value_enum, default_value_t = file::Backup::None)]
+    pub backup: file::Backup,
+    /// Keys of the text box, vim adds modal editing and emacs its own keys
+    #[arg(long, value_enum, default_value_t = key_binds::Profile::Standard)]
+    pub profile: key_binds::Profile,
 }
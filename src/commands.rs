@@ -0,0 +1,535 @@
+use iced::keyboard::{Key, Modifiers, key::Named};
+
+use crate::{
+    Message,
+    key_binds::{JUMP, KeyBind},
+    search,
+    text_box::{Binding, BindingMotion},
+};
+
+// an action reachable by name. the palette, the key bindings and menus are all
+// built from the registry
+pub struct Command {
+    // stable, for keymaps
+    pub id: &'static str,
+    pub title: &'static str,
+    pub action: Action,
+    // default shortcuts
+    pub keys: Vec<KeyBind>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    App(Message),
+    // runs in the focused editor
+    Editor(Binding),
+}
+
+pub fn registry() -> Vec<Command> {
+    let mut commands = app();
+    commands.extend(editor());
+    commands
+}
+
+fn app() -> Vec<Command> {
+    vec![
+        command("file.new", "New File", Message::NewFile, [ctrl("n")]),
+        command("file.open", "Open File...", Message::OpenFileSelector, []),
+        command(
+            "file.open_directory",
+            "Open Directory...",
+            Message::OpenDirectorySelector,
+            [],
+        ),
+        command(
+            "file.quick_open",
+            "Quick Open",
+            Message::QuickOpenShow,
+            [ctrl("p")],
+        ),
+        command("file.save", "Save", Message::SaveFile, [ctrl("s")]),
+        command(
+            "file.save_as",
+            "Save As...",
+            Message::SaveFileAs,
+            [ctrl_shift("s")],
+        ),
+        command(
+            "tab.close",
+            "Close Tab",
+            Message::TabCloseCurrent,
+            [ctrl("w")],
+        ),
+        command("tab.1", "Go to Tab 1", Message::TabSelected(0), [alt("1")]),
+        command("tab.2", "Go to Tab 2", Message::TabSelected(1), [alt("2")]),
+        command("tab.3", "Go to Tab 3", Message::TabSelected(2), [alt("3")]),
+        command("tab.4", "Go to Tab 4", Message::TabSelected(3), [alt("4")]),
+        command("tab.5", "Go to Tab 5", Message::TabSelected(4), [alt("5")]),
+        command("tab.6", "Go to Tab 6", Message::TabSelected(5), [alt("6")]),
+        command("tab.7", "Go to Tab 7", Message::TabSelected(6), [alt("7")]),
+        command("tab.8", "Go to Tab 8", Message::TabSelected(7), [alt("8")]),
+        command("tab.9", "Go to Tab 9", Message::TabSelected(8), [alt("9")]),
+        command(
+            "tab.next",
+            "Next Tab",
+            Message::TabNext,
+            [named(Modifiers::CTRL, Named::PageDown)],
+        ),
+        command(
+            "tab.previous",
+            "Previous Tab",
+            Message::TabPrevious,
+            [named(Modifiers::CTRL, Named::PageUp)],
+        ),
+        command(
+            "tab.reload",
+            "Reload from Disk",
+            Message::TabConflictReload,
+            [],
+        ),
+        command("tab.keep", "Keep My Changes", Message::TabConflictKeep, []),
+        command(
+            "tab.diff",
+            "Toggle Diff with Disk",
+            Message::TabConflictDiff,
+            [],
+        ),
+        command("search.open", "Find", Message::TabSearchOpen, [ctrl("f")]),
+        command(
+            "search.next",
+            "Find Next",
+            Message::TabSearchNext,
+            [named(Modifiers::empty(), Named::F3)],
+        ),
+        command(
+            "search.previous",
+            "Find Previous",
+            Message::TabSearchPrevious,
+            [named(Modifiers::SHIFT, Named::F3)],
+        ),
+        command("search.close", "Close Find", Message::TabSearchClose, []),
+        command(
+            "search.case_sensitive",
+            "Toggle Search Case Sensitive",
+            Message::TabSearchToggle(search::Toggle::CaseSensitive),
+            [],
+        ),
+        command(
+            "search.whole_word",
+            "Toggle Search Whole Word",
+            Message::TabSearchToggle(search::Toggle::WholeWord),
+            [],
+        ),
+        command(
+            "search.regex",
+            "Toggle Search Regex",
+            Message::TabSearchToggle(search::Toggle::Regex),
+            [],
+        ),
+        command(
+            "search.preserve_case",
+            "Toggle Replace Preserve Case",
+            Message::TabSearchToggle(search::Toggle::PreserveCase),
+            [],
+        ),
+        command(
+            "search.in_selection",
+            "Toggle Search in Selection",
+            Message::TabSearchToggle(search::Toggle::InSelection),
+            [],
+        ),
+        command(
+            "search.replace_all",
+            "Replace All",
+            Message::TabReplaceAll,
+            [],
+        ),
+        command(
+            "project_search.open",
+            "Search Project",
+            Message::ProjectSearchOpen,
+            [ctrl_shift("f")],
+        ),
+        command(
+            "project_search.replace_all",
+            "Replace All in Project",
+            Message::ProjectSearchReplaceAll,
+            [],
+        ),
+        command(
+            "tree.focus",
+            "Toggle Project Tree Focus",
+            Message::ProjectTreeFocusToggle,
+            [ctrl_shift("e")],
+        ),
+        command(
+            "tree.menu",
+            "Project Tree Actions",
+            Message::ProjectTreeMenuToggle,
+            [
+                named(Modifiers::SHIFT, Named::F10),
+                named(Modifiers::empty(), Named::ContextMenu),
+            ],
+        ),
+        command(
+            "tree.reveal",
+            "Reveal Active File in Project Tree",
+            Message::ProjectTreeRevealActive,
+            [],
+        ),
+        command(
+            "tree.follow",
+            "Toggle Project Tree Follows Active File",
+            Message::ProjectTreeToggleFollow,
+            [],
+        ),
+        command(
+            "tree.hidden",
+            "Toggle Hidden Files in Project Tree",
+            Message::ProjectTreeToggleHidden,
+            [],
+        ),
+        command(
+            "tree.collapse",
+            "Collapse Project Tree",
+            Message::ProjectTreeCollapseAll,
+            [],
+        ),
+        command(
+            "palette.open",
+            "Command Palette",
+            Message::PaletteShow,
+            [ctrl_shift("p")],
+        ),
+        command("error.dismiss", "Dismiss Error", Message::DismissError, []),
+    ]
+}
+
+// editing keys of the text box
+fn editor() -> Vec<Command> {
+    use BindingMotion::*;
+    let none = Modifiers::empty();
+    let shift = Modifiers::SHIFT;
+    let block = Modifiers::ALT | Modifiers::SHIFT;
+    let cmd = Modifiers::COMMAND;
+
+    vec![
+        edit(
+            "editor.escape",
+            "Clear Carets",
+            Binding::Escape,
+            [named(none, Named::Escape), named(shift, Named::Escape)],
+        ),
+        edit(
+            "editor.newline",
+            "New Line",
+            Binding::Enter,
+            [named(none, Named::Enter), named(shift, Named::Enter)],
+        ),
+        edit(
+            "editor.indent",
+            "Indent",
+            Binding::Tab,
+            [named(none, Named::Tab)],
+        ),
+        edit(
+            "editor.unindent",
+            "Unindent",
+            Binding::Unindent,
+            [named(shift, Named::Tab)],
+        ),
+        edit(
+            "editor.backspace",
+            "Delete Backward",
+            Binding::Backspace,
+            [
+                named(none, Named::Backspace),
+                named(shift, Named::Backspace),
+            ],
+        ),
+        edit(
+            "editor.backspace_word",
+            "Delete Word Backward",
+            Binding::BackspaceWord,
+            [named(cmd, Named::Backspace)],
+        ),
+        edit(
+            "editor.delete",
+            "Delete Forward",
+            Binding::Delete,
+            [named(none, Named::Delete), named(shift, Named::Delete)],
+        ),
+        edit(
+            "editor.delete_word",
+            "Delete Word Forward",
+            Binding::DeleteWord,
+            [named(cmd, Named::Delete)],
+        ),
+        edit("editor.copy", "Copy", Binding::Copy, [character(cmd, "c")]),
+        edit("editor.cut", "Cut", Binding::Cut, [character(cmd, "x")]),
+        edit(
+            "editor.paste",
+            "Paste",
+            Binding::Paste,
+            [character(cmd, "v")],
+        ),
+        edit(
+            "editor.select_all",
+            "Select All",
+            Binding::SelectAll,
+            [character(cmd, "a")],
+        ),
+        edit(
+            "editor.add_next_occurrence",
+            "Add Next Occurrence",
+            Binding::AddNextOccurrence,
+            [character(cmd, "d")],
+        ),
+        edit(
+            "editor.select_all_occurrences",
+            "Select All Occurrences",
+            Binding::SelectAllOccurrences,
+            [character(cmd | shift, "l")],
+        ),
+        edit("editor.undo", "Undo", Binding::Undo, [character(cmd, "z")]),
+        edit(
+            "editor.redo",
+            "Redo",
+            Binding::Redo,
+            [character(cmd | shift, "z")],
+        ),
+        edit("editor.kill_line", "Kill Line", Binding::KillLine, []),
+        edit("editor.yank", "Yank", Binding::Yank, []),
+        edit("editor.yank_pop", "Yank Pop", Binding::YankPop, []),
+        edit("editor.set_mark", "Set Mark", Binding::SetMark, []),
+        edit(
+            "editor.move_left",
+            "Move Left",
+            Binding::Move(Left),
+            [named(none, Named::ArrowLeft)],
+        ),
+        edit(
+            "editor.move_right",
+            "Move Right",
+            Binding::Move(Right),
+            [named(none, Named::ArrowRight)],
+        ),
+        edit(
+            "editor.move_up",
+            "Move Up",
+            Binding::Move(Up),
+            [named(none, Named::ArrowUp)],
+        ),
+        edit(
+            "editor.move_down",
+            "Move Down",
+            Binding::Move(Down),
+            [named(none, Named::ArrowDown)],
+        ),
+        edit(
+            "editor.move_page_up",
+            "Move Page Up",
+            Binding::Move(PageUp),
+            [named(none, Named::PageUp)],
+        ),
+        edit(
+            "editor.move_page_down",
+            "Move Page Down",
+            Binding::Move(PageDown),
+            [named(none, Named::PageDown)],
+        ),
+        edit(
+            "editor.move_home",
+            "Move to Line Start",
+            Binding::Move(Home),
+            [named(none, Named::Home)]
+                .into_iter()
+                .chain(macos(named(Modifiers::LOGO, Named::ArrowLeft))),
+        ),
+        edit(
+            "editor.move_end",
+            "Move to Line End",
+            Binding::Move(End),
+            [named(none, Named::End)]
+                .into_iter()
+                .chain(macos(named(Modifiers::LOGO, Named::ArrowRight))),
+        ),
+        edit(
+            "editor.move_word_left",
+            "Move Word Left",
+            Binding::Move(WordLeft),
+            [named(JUMP, Named::ArrowLeft)],
+        ),
+        edit(
+            "editor.move_word_right",
+            "Move Word Right",
+            Binding::Move(WordRight),
+            [named(JUMP, Named::ArrowRight)],
+        ),
+        edit(
+            "editor.move_document_start",
+            "Move to Document Start",
+            Binding::Move(DocumentStart),
+            [named(JUMP, Named::Home)],
+        ),
+        edit(
+            "editor.move_document_end",
+            "Move to Document End",
+            Binding::Move(DocumentEnd),
+            [named(JUMP, Named::End)],
+        ),
+        edit(
+            "editor.select_left",
+            "Select Left",
+            Binding::Select(Left),
+            [named(shift, Named::ArrowLeft)],
+        ),
+        edit(
+            "editor.select_right",
+            "Select Right",
+            Binding::Select(Right),
+            [named(shift, Named::ArrowRight)],
+        ),
+        edit(
+            "editor.select_up",
+            "Select Up",
+            Binding::Select(Up),
+            [named(shift, Named::ArrowUp)],
+        ),
+        edit(
+            "editor.select_down",
+            "Select Down",
+            Binding::Select(Down),
+            [named(shift, Named::ArrowDown)],
+        ),
+        edit(
+            "editor.select_page_up",
+            "Select Page Up",
+            Binding::Select(PageUp),
+            [named(shift, Named::PageUp)],
+        ),
+        edit(
+            "editor.select_page_down",
+            "Select Page Down",
+            Binding::Select(PageDown),
+            [named(shift, Named::PageDown)],
+        ),
+        edit(
+            "editor.select_home",
+            "Select to Line Start",
+            Binding::Select(Home),
+            [named(shift, Named::Home)]
+                .into_iter()
+                .chain(macos(named(Modifiers::LOGO | shift, Named::ArrowLeft))),
+        ),
+        edit(
+            "editor.select_end",
+            "Select to Line End",
+            Binding::Select(End),
+            [named(shift, Named::End)]
+                .into_iter()
+                .chain(macos(named(Modifiers::LOGO | shift, Named::ArrowRight))),
+        ),
+        edit(
+            "editor.select_word_left",
+            "Select Word Left",
+            Binding::Select(WordLeft),
+            [named(JUMP | shift, Named::ArrowLeft)],
+        ),
+        edit(
+            "editor.select_word_right",
+            "Select Word Right",
+            Binding::Select(WordRight),
+            [named(JUMP | shift, Named::ArrowRight)],
+        ),
+        edit(
+            "editor.select_document_start",
+            "Select to Document Start",
+            Binding::Select(DocumentStart),
+            [named(JUMP | shift, Named::Home)],
+        ),
+        edit(
+            "editor.select_document_end",
+            "Select to Document End",
+            Binding::Select(DocumentEnd),
+            [named(JUMP | shift, Named::End)],
+        ),
+        edit(
+            "editor.block_left",
+            "Grow Block Left",
+            Binding::SelectBlock(Left),
+            [named(block, Named::ArrowLeft)],
+        ),
+        edit(
+            "editor.block_right",
+            "Grow Block Right",
+            Binding::SelectBlock(Right),
+            [named(block, Named::ArrowRight)],
+        ),
+        edit(
+            "editor.block_up",
+            "Grow Block Up",
+            Binding::SelectBlock(Up),
+            [named(block, Named::ArrowUp)],
+        ),
+        edit(
+            "editor.block_down",
+            "Grow Block Down",
+            Binding::SelectBlock(Down),
+            [named(block, Named::ArrowDown)],
+        ),
+    ]
+}
+
+fn command(
+    id: &'static str,
+    title: &'static str,
+    message: Message,
+    keys: impl IntoIterator<Item = KeyBind>,
+) -> Command {
+    Command {
+        id,
+        title,
+        action: Action::App(message),
+        keys: keys.into_iter().collect(),
+    }
+}
+
+fn edit(
+    id: &'static str,
+    title: &'static str,
+    binding: Binding,
+    keys: impl IntoIterator<Item = KeyBind>,
+) -> Command {
+    Command {
+        id,
+        title,
+        action: Action::Editor(binding),
+        keys: keys.into_iter().collect(),
+    }
+}
+
+// a key only macOS has by default
+fn macos(key: KeyBind) -> Option<KeyBind> {
+    cfg!(target_os = "macos").then_some(key)
+}
+
+fn ctrl(key: &str) -> KeyBind {
+    character(Modifiers::CTRL, key)
+}
+
+fn ctrl_shift(key: &str) -> KeyBind {
+    character(Modifiers::CTRL | Modifiers::SHIFT, key)
+}
+
+fn alt(key: &str) -> KeyBind {
+    character(Modifiers::ALT, key)
+}
+
+fn character(modifiers: Modifiers, key: &str) -> KeyBind {
+    KeyBind::new(modifiers, Key::Character(key.into()))
+}
+
+fn named(modifiers: Modifiers, key: Named) -> KeyBind {
+    KeyBind::new(modifiers, Key::Named(key))
+}
@@ -0,0 +1,182 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+// copy of the previous content kept next to a saved file
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Backup {
+    #[default]
+    None,
+    // file.txt~
+    Tilde,
+    // file.txt.bak
+    Bak,
+}
+
+// writes a document, through a symlink so the link itself survives,
+// after copying what was there to the backup
+pub fn save(path: &Path, contents: &[u8], backup: Backup) -> io::Result<()> {
+    let path = match fs::canonicalize(path) {
+        Ok(path) => path,
+        // a new file
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(err) => return Err(err),
+    };
+
+    if let Some(backup_path) = backup_path(&path, backup)
+        && path.exists()
+    {
+        fs::copy(&path, backup_path)?;
+    }
+    write_atomic(&path, contents)
+}
+
+fn backup_path(path: &Path, backup: Backup) -> Option<PathBuf> {
+    let suffix = match backup {
+        Backup::None => return None,
+        Backup::Tilde => "~",
+        Backup::Bak => ".bak",
+    };
+    let mut name = OsString::from(path.file_name()?);
+    name.push(suffix);
+    Some(path.with_file_name(name))
+}
+
+// writes a temporary file next to path and renames it over path,
+// so a crash mid-write never leaves a truncated file behind
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path.parent().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "path has no parent directory")
+    })?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = dir.join(temp_name);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+            keep_owner(&file, &metadata)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // makes the rename itself durable
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn keep_owner(file: &File, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, fchown};
+
+    match fchown(file, Some(metadata.uid()), Some(metadata.gid())) {
+        // only root can give files away, the file then belongs to whoever saved it
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_file: &File, _metadata: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+// copies a file or directory next to itself as "name copy.ext", "name copy 2.ext", ...
+pub fn duplicate(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    // directories keep dots in their name
+    let (stem, extension) = if path.is_dir() {
+        (name.to_string_lossy(), String::new())
+    } else {
+        (
+            path.file_stem().unwrap_or(name).to_string_lossy(),
+            path.extension()
+                .map_or(String::new(), |ext| format!(".{}", ext.to_string_lossy())),
+        )
+    };
+
+    let copy = (1..)
+        .map(|n| {
+            let name = if n == 1 {
+                format!("{} copy{}", stem, extension)
+            } else {
+                format!("{} copy {}{}", stem, n, extension)
+            };
+            path.with_file_name(name)
+        })
+        .find(|copy| !copy.exists())
+        .unwrap();
+    copy_recursive(path, &copy)?;
+    Ok(copy)
+}
+
+// symlinks are copied as links, following one to a parent would never end
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let kind = fs::symlink_metadata(from)?.file_type();
+    if kind.is_symlink() {
+        let target = fs::read_link(from)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, to);
+        #[cfg(windows)]
+        return if from.is_dir() {
+            std::os::windows::fs::symlink_dir(target, to)
+        } else {
+            std::os::windows::fs::symlink_file(target, to)
+        };
+    }
+    if kind.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+// moves to the desktop trash, so it can be restored
+pub fn trash(path: &Path) -> io::Result<()> {
+    trash::delete(path).map_err(io::Error::other)
+}
+
+// shows the directory containing path in the file manager
+pub fn reveal(path: &Path) -> io::Result<()> {
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+
+    #[cfg(target_os = "macos")]
+    let opener = "open";
+    #[cfg(target_os = "windows")]
+    let opener = "explorer";
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let opener = "xdg-open";
+
+    std::process::Command::new(opener)
+        .arg(dir)
+        .spawn()
+        .map(|_| ())
+}
@@ -44,3 +44,60 @@ pub fn caret_down() -> char {
 pub fn caret_right() -> char {
     '\u{f105}'
 }
+
+// solid icons of files
+const FILE: char = '\u{f15b}';
+const FILE_CODE: char = '\u{f1c9}';
+const FILE_LINES: char = '\u{f15c}';
+const FILE_CSV: char = '\u{f6dd}';
+const FILE_IMAGE: char = '\u{f1c5}';
+const FILE_PDF: char = '\u{f1c1}';
+const FILE_ZIPPER: char = '\u{f1c6}';
+const FILE_AUDIO: char = '\u{f1c7}';
+const FILE_VIDEO: char = '\u{f1c8}';
+const BOX: char = '\u{f466}';
+const CODE_BRANCH: char = '\u{f126}';
+const BOOK: char = '\u{f02d}';
+const SCALE_BALANCED: char = '\u{f24e}';
+const TERMINAL: char = '\u{f120}';
+const GEAR: char = '\u{f013}';
+const LOCK: char = '\u{f023}';
+const DATABASE: char = '\u{f1c0}';
+
+// icon of a file, by special name first and extension then
+pub fn file_icon(name: &str) -> char {
+    let lower = name.to_lowercase();
+    match lower.as_str() {
+        "cargo.toml" | "cargo.lock" | "package.json" | "package-lock.json" => return BOX,
+        ".gitignore" | ".gitattributes" | ".gitmodules" | ".ignore" => return CODE_BRANCH,
+        _ => {}
+    }
+    if lower.starts_with("readme") {
+        return BOOK;
+    }
+    if lower.starts_with("license") || lower.starts_with("licence") {
+        return SCALE_BALANCED;
+    }
+
+    let extension = lower
+        .rsplit_once('.')
+        .map_or("", |(_, extension)| extension);
+    match extension {
+        "rs" | "c" | "h" | "cpp" | "hpp" | "cc" | "go" | "py" | "js" | "ts" | "jsx" | "tsx"
+        | "java" | "kt" | "swift" | "rb" | "lua" | "zig" | "html" | "css" | "scss" | "xml" => {
+            FILE_CODE
+        }
+        "sh" | "bash" | "zsh" | "fish" | "ps1" | "bat" => TERMINAL,
+        "toml" | "json" | "yaml" | "yml" | "ini" | "cfg" | "conf" => GEAR,
+        "md" | "txt" | "rst" | "adoc" => FILE_LINES,
+        "csv" | "tsv" => FILE_CSV,
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "ico" | "bmp" => FILE_IMAGE,
+        "pdf" => FILE_PDF,
+        "zip" | "gz" | "tar" | "xz" | "bz2" | "7z" | "zst" => FILE_ZIPPER,
+        "mp3" | "wav" | "ogg" | "flac" => FILE_AUDIO,
+        "mp4" | "mkv" | "webm" | "mov" => FILE_VIDEO,
+        "lock" => LOCK,
+        "db" | "sqlite" | "sql" => DATABASE,
+        _ => FILE,
+    }
+}
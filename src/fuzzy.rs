@@ -0,0 +1,46 @@
+// scores how well pattern matches candidate as an in-order subsequence, ignoring
+// case. None when it does not match at all, otherwise higher is better: characters
+// that follow each other or start a word count more, skipped ones count against
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let mut pattern = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut last: Option<usize> = None;
+    let mut previous: Option<char> = None;
+
+    for (index, c) in candidate.chars().enumerate() {
+        let Some(&wanted) = pattern.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(wanted.to_lowercase()) {
+            score += 16;
+            match last {
+                Some(last) if last + 1 == index => score += 8,
+                Some(last) => score -= (index - last - 1) as i64,
+                // a late first match costs a little
+                None => score -= index.min(8) as i64,
+            }
+            if is_word_start(previous, c) {
+                score += 8;
+            }
+            last = Some(index);
+            pattern.next();
+        }
+        previous = Some(c);
+    }
+
+    if pattern.peek().is_some() {
+        return None;
+    }
+    Some(score)
+}
+
+// first character, after a separator or a lowercase to uppercase change
+fn is_word_start(previous: Option<char>, c: char) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            matches!(previous, '_' | '-' | '.' | ' ' | '/' | '\\')
+                || (previous.is_lowercase() && c.is_uppercase())
+        }
+    }
+}
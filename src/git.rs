@@ -0,0 +1,105 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// state of a path in its git working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Modified,
+    Added,
+    Untracked,
+    Ignored,
+    Conflicted,
+}
+
+// git status of a working tree by absolute path, read with the git command
+#[derive(Debug, Clone, Default)]
+pub struct Status {
+    paths: HashMap<PathBuf, State>,
+    // directories holding a change, ignored entries aside
+    changed: HashSet<PathBuf>,
+}
+
+impl Status {
+    // status of the repository dir is in, empty when it is in none
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let top = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["rev-parse", "--show-toplevel"])
+            .output()?;
+        if !top.status.success() {
+            return Ok(Self::default());
+        }
+        let top = PathBuf::from(String::from_utf8_lossy(&top.stdout).trim_end());
+
+        // optional locks would rewrite the index, which the directory watcher sees
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&top)
+            .args([
+                "--no-optional-locks",
+                "status",
+                "--porcelain=v1",
+                "-z",
+                "--ignored",
+            ])
+            .output()?;
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(err.trim().to_string()));
+        }
+
+        let mut status = Self::default();
+        let mut fields = output.stdout.split(|byte| *byte == 0);
+        while let Some(field) = fields.next() {
+            // "XY path"
+            if field.len() < 4 {
+                continue;
+            }
+            let (xy, path) = (&field[..2], &field[3..]);
+            // renames and copies are followed by the original path
+            if matches!(xy[0], b'R' | b'C') {
+                fields.next();
+            }
+            let state = match xy {
+                b"??" => State::Untracked,
+                b"!!" => State::Ignored,
+                b"DD" | b"AU" | b"UD" | b"UA" | b"DU" | b"AA" | b"UU" => State::Conflicted,
+                [b'A', _] => State::Added,
+                _ => State::Modified,
+            };
+            // untracked and ignored directories end with a slash
+            let path = top.join(String::from_utf8_lossy(path).trim_end_matches('/'));
+
+            if state != State::Ignored {
+                for dir in path.ancestors().skip(1) {
+                    if !status.changed.insert(dir.to_path_buf()) || dir == top {
+                        break;
+                    }
+                }
+            }
+            status.paths.insert(path, state);
+        }
+        Ok(status)
+    }
+
+    // an untracked or ignored directory passes its state to everything in it
+    pub fn state(&self, path: &Path) -> Option<State> {
+        if let Some(state) = self.paths.get(path) {
+            return Some(*state);
+        }
+        path.ancestors()
+            .skip(1)
+            .filter_map(|dir| self.paths.get(dir))
+            .find(|state| matches!(state, State::Untracked | State::Ignored))
+            .copied()
+    }
+
+    pub fn is_changed(&self, dir: &Path) -> bool {
+        self.changed.contains(dir)
+    }
+}
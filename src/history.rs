@@ -0,0 +1,488 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use cosmic_text::{Change, ChangeItem, Cursor};
+use serde::{Deserialize, Serialize};
+
+use crate::file;
+
+// steps kept when no limit is given on the command line
+pub const DEFAULT_LIMIT: usize = 1000;
+// typing after a pause this long starts a new undo step
+const COALESCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// what an edit did, runs of the same kind are undone together
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Kind {
+    Insert,
+    Delete,
+    Other,
+}
+
+#[derive(Clone)]
+struct Step {
+    change: Change,
+    kind: Kind,
+    // items of a single edit, more than one for edits at several carets
+    width: usize,
+}
+
+// undo and redo stacks of a document
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    limit: usize,
+    // undo length when the document was saved, None if that state can't be reached anymore
+    saved: Option<usize>,
+    // when the last step was pushed, to coalesce with it
+    last_push: Option<Instant>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit,
+            saved: Some(0),
+            last_push: None,
+        }
+    }
+
+    pub fn push(&mut self, change: Change, kind: Kind) {
+        if change.items.is_empty() {
+            return;
+        }
+
+        // the saved state was undone and is dropped with the redo stack
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+
+        let now = Instant::now();
+        let recent = self
+            .last_push
+            .is_some_and(|last_push| now.duration_since(last_push) < COALESCE_TIMEOUT);
+        self.last_push = Some(now);
+
+        if recent
+            && self.saved != Some(self.undo.len())
+            && let Some(last) = self.undo.last_mut()
+            && coalesces(last, &change, kind)
+        {
+            last.change.items.extend(change.items);
+            return;
+        }
+
+        let width = change.items.len();
+        self.undo.push(Step {
+            change,
+            kind,
+            width,
+        });
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    // change to apply to undo the last step
+    pub fn undo(&mut self) -> Option<Change> {
+        let step = self.undo.pop()?;
+        let mut change = step.change.clone();
+        change.reverse();
+        self.redo.push(step);
+        self.last_push = None;
+        Some(change)
+    }
+
+    // change to apply to redo the last undone step, it can be undone again
+    pub fn redo(&mut self) -> Option<Change> {
+        let step = self.redo.pop()?;
+        let change = step.change.clone();
+        self.undo.push(step);
+        self.last_push = None;
+        Some(change)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+        self.last_push = None;
+    }
+
+    // the file changed under the document, no state of the history matches it
+    pub fn forget_saved(&mut self) {
+        self.saved = None;
+    }
+
+    // for a document that was read again, old steps don't apply to it
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.saved = Some(0);
+        self.last_push = None;
+    }
+
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+
+    // writes the history to the cache directory, wound back to the saved state,
+    // which is what the file on disk holds
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let Some(saved) = self.saved else {
+            // the file on disk matches no state of the history
+            return Ok(());
+        };
+        if self.undo.is_empty() && self.redo.is_empty() {
+            return Ok(());
+        }
+
+        let path = fs::canonicalize(path)?;
+        let hash = fnv_hash(&fs::read(&path)?);
+        let (undo, redo) = self.wound_to(saved);
+
+        let stored = Stored {
+            path: path.clone(),
+            hash,
+            undo: undo.iter().map(StoredStep::from).collect(),
+            redo: redo.iter().map(StoredStep::from).collect(),
+        };
+        let cache_path = cache_path(&path)?;
+        if let Some(dir) = cache_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        file::write_atomic(&cache_path, &serde_json::to_vec(&stored)?)
+    }
+
+    // undo and redo stacks as if undone or redone until the undo stack is saved long
+    fn wound_to(&self, saved: usize) -> (Vec<Step>, Vec<Step>) {
+        let mut undo = self.undo.clone();
+        let mut redo = self.redo.clone();
+        while undo.len() > saved {
+            redo.extend(undo.pop());
+        }
+        while undo.len() < saved {
+            undo.extend(redo.pop());
+        }
+        (undo, redo)
+    }
+
+    // reads the history stored for path, if the file still reads the same as when it was stored
+    pub fn restore(&mut self, path: &Path) -> io::Result<()> {
+        let path = fs::canonicalize(path)?;
+        let stored = match fs::read(cache_path(&path)?) {
+            Ok(stored) => stored,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let stored: Stored = serde_json::from_slice(&stored)?;
+        if stored.path != path || stored.hash != fnv_hash(&fs::read(&path)?) {
+            return Ok(());
+        }
+
+        self.undo = stored.undo.into_iter().map(Step::from).collect();
+        self.redo = stored.redo.into_iter().map(Step::from).collect();
+        // keeps the newest steps
+        let over = self.undo.len().saturating_sub(self.limit);
+        self.undo.drain(..over);
+        self.saved = Some(self.undo.len());
+        self.last_push = None;
+        Ok(())
+    }
+}
+
+// history as written to the cache directory
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    path: PathBuf,
+    // of the file content the history ends at
+    hash: u64,
+    undo: Vec<StoredStep>,
+    redo: Vec<StoredStep>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredStep {
+    kind: Kind,
+    width: usize,
+    items: Vec<StoredItem>,
+}
+
+// cosmic_text::ChangeItem with cursors as (line, index)
+#[derive(Serialize, Deserialize)]
+struct StoredItem {
+    start: (usize, usize),
+    end: (usize, usize),
+    text: String,
+    insert: bool,
+}
+
+impl From<&Step> for StoredStep {
+    fn from(step: &Step) -> Self {
+        Self {
+            kind: step.kind,
+            width: step.width,
+            items: step
+                .change
+                .items
+                .iter()
+                .map(|item| StoredItem {
+                    start: (item.start.line, item.start.index),
+                    end: (item.end.line, item.end.index),
+                    text: item.text.clone(),
+                    insert: item.insert,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<StoredStep> for Step {
+    fn from(step: StoredStep) -> Self {
+        Self {
+            change: Change {
+                items: step
+                    .items
+                    .into_iter()
+                    .map(|item| ChangeItem {
+                        start: Cursor::new(item.start.0, item.start.1),
+                        end: Cursor::new(item.end.0, item.end.1),
+                        text: item.text,
+                        insert: item.insert,
+                    })
+                    .collect(),
+            },
+            kind: step.kind,
+            width: step.width,
+        }
+    }
+}
+
+// one file per document, named by the hash of its canonical path
+fn cache_path(path: &Path) -> io::Result<PathBuf> {
+    let cache_dir = dirs::cache_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    let name = format!(
+        "{:016x}.json",
+        fnv_hash(path.as_os_str().as_encoded_bytes())
+    );
+    Ok(cache_dir.join("editorium").join("history").join(name))
+}
+
+// FNV-1a, stable across runs unlike the std hasher
+pub fn fnv_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+// typing, backspace and delete runs merge until a word ends or the cursor jumps
+fn coalesces(last: &Step, change: &Change, kind: Kind) -> bool {
+    if kind == Kind::Other || last.kind != kind {
+        return false;
+    }
+    if change.items.len() != last.width {
+        return false;
+    }
+    let (Some(prev), Some(next)) = (last.change.items.last(), change.items.last()) else {
+        return false;
+    };
+    // edits at several carets have no single position to follow,
+    // the last items are both at the first caret
+    if last.width > 1 {
+        return !word_ends(prev, next);
+    }
+
+    match kind {
+        Kind::Insert => {
+            prev.insert && next.insert && at(next.start, prev.end) && !word_ends(prev, next)
+        }
+        // backspace deletes before the last deletion, delete at the same place
+        Kind::Delete => {
+            !prev.insert && !next.insert && (at(next.end, prev.start) || at(next.start, prev.start))
+        }
+        Kind::Other => false,
+    }
+}
+
+// whitespace typed after a word is kept with it, the next word starts a new step
+fn word_ends(prev: &ChangeItem, next: &ChangeItem) -> bool {
+    let prev_space = prev.text.chars().last().is_some_and(char::is_whitespace);
+    let next_space = next.text.chars().next().is_some_and(char::is_whitespace);
+    prev_space && !next_space
+}
+
+// cursor affinity doesn't matter for where an edit happened
+fn at(a: Cursor, b: Cursor) -> bool {
+    a.line == b.line && a.index == b.index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(line: usize, index: usize, text: &str) -> Change {
+        let end = Cursor::new(line, index + text.len());
+        item(Cursor::new(line, index), end, text, true)
+    }
+
+    // the text that was before line, index
+    fn backspace(line: usize, index: usize, text: &str) -> Change {
+        let start = Cursor::new(line, index - text.len());
+        item(start, Cursor::new(line, index), text, false)
+    }
+
+    fn item(start: Cursor, end: Cursor, text: &str, insert: bool) -> Change {
+        Change {
+            items: vec![ChangeItem {
+                start,
+                end,
+                text: text.to_string(),
+                insert,
+            }],
+        }
+    }
+
+    // texts of the items each undo gives back, newest step first
+    fn steps(history: &mut History) -> Vec<Vec<String>> {
+        let mut steps = Vec::new();
+        while let Some(change) = history.undo() {
+            let mut texts: Vec<String> = change.items.into_iter().map(|item| item.text).collect();
+            // undo hands the items back reversed
+            texts.reverse();
+            steps.push(texts);
+        }
+        steps
+    }
+
+    #[test]
+    fn typing_run_coalesces() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        for (index, text) in ["a", "b", "c"].into_iter().enumerate() {
+            history.push(insert(0, index, text), Kind::Insert);
+        }
+        assert_eq!(steps(&mut history), [["a", "b", "c"]]);
+    }
+
+    #[test]
+    fn space_then_word_splits() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        for (index, text) in ["a", "b", " ", "c"].into_iter().enumerate() {
+            history.push(insert(0, index, text), Kind::Insert);
+        }
+        assert_eq!(steps(&mut history), vec![vec!["c"], vec!["a", "b", " "]]);
+    }
+
+    #[test]
+    fn jumps_and_kinds_split() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        history.push(insert(0, 0, "a"), Kind::Insert);
+        // somewhere else
+        history.push(insert(3, 0, "b"), Kind::Insert);
+        history.push(backspace(3, 1, "b"), Kind::Delete);
+        history.push(insert(3, 0, "c"), Kind::Other);
+        history.push(insert(3, 1, "d"), Kind::Other);
+        assert_eq!(steps(&mut history).len(), 5);
+    }
+
+    #[test]
+    fn backspace_run_coalesces() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        history.push(backspace(0, 3, "c"), Kind::Delete);
+        history.push(backspace(0, 2, "b"), Kind::Delete);
+        history.push(backspace(0, 1, "a"), Kind::Delete);
+        assert_eq!(steps(&mut history), [["c", "b", "a"]]);
+    }
+
+    #[test]
+    fn saved_step_is_not_extended() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        history.push(insert(0, 0, "a"), Kind::Insert);
+        history.mark_saved();
+        history.push(insert(0, 1, "b"), Kind::Insert);
+        assert!(!history.is_saved());
+        assert_eq!(steps(&mut history), [["b"], ["a"]]);
+    }
+
+    #[test]
+    fn limit_shifts_saved() {
+        let mut history = History::new(2);
+        history.push(insert(0, 0, "a"), Kind::Other);
+        history.mark_saved();
+        history.push(insert(0, 1, "b"), Kind::Other);
+        history.push(insert(0, 2, "c"), Kind::Other);
+        // a is gone, the saved state is now at the bottom of the stack
+        history.undo();
+        history.undo();
+        assert!(history.undo().is_none());
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn limit_evicts_saved() {
+        let mut history = History::new(2);
+        history.mark_saved();
+        for (index, text) in ["a", "b", "c"].into_iter().enumerate() {
+            history.push(insert(0, index, text), Kind::Other);
+        }
+        // the saved state was before a, which can't be undone anymore
+        while history.undo().is_some() {
+            assert!(!history.is_saved());
+        }
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn edit_after_undo_drops_saved() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        history.push(insert(0, 0, "a"), Kind::Other);
+        history.mark_saved();
+        history.undo();
+        assert!(!history.is_saved());
+        history.push(insert(0, 0, "b"), Kind::Other);
+        assert!(!history.is_saved());
+        history.undo();
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn redo_reaches_saved() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        history.push(insert(0, 0, "a"), Kind::Other);
+        history.mark_saved();
+        history.undo();
+        history.redo();
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn winds_to_saved() {
+        let mut history = History::new(DEFAULT_LIMIT);
+        for (index, text) in ["a", "b", "c"].into_iter().enumerate() {
+            history.push(insert(0, index, text), Kind::Other);
+        }
+        history.undo();
+        // saved with a and b, c is to redo
+        let texts = |steps: &[Step]| -> Vec<String> {
+            steps
+                .iter()
+                .map(|step| step.change.items[0].text.clone())
+                .collect()
+        };
+        let (undo, redo) = history.wound_to(1);
+        assert_eq!(texts(&undo), ["a"]);
+        // redo pops from the end, b comes back first
+        assert_eq!(texts(&redo), ["c", "b"]);
+        let (undo, redo) = history.wound_to(3);
+        assert_eq!(texts(&undo), ["a", "b", "c"]);
+        assert!(redo.is_empty());
+    }
+}
@@ -1,61 +1,397 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::PathBuf,
+    sync::Mutex,
+};
 
-use iced::keyboard::{Key, Modifiers};
+use iced::keyboard::{Key, Modifiers, key::Named};
 
-use crate::Message;
+use crate::commands::{Action, Command};
 
-#[derive(Hash, PartialEq, Eq)]
+// moves by words and to the ends of the document
+pub const JUMP: Modifiers = if cfg!(target_os = "macos") {
+    Modifiers::ALT
+} else {
+    Modifiers::CTRL
+};
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct KeyBind {
     pub modifiers: Modifiers,
     pub key: Key,
 }
 
-pub fn default() -> HashMap<KeyBind, Message> {
-    let mut key_bind = HashMap::new();
-
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::CTRL,
-            key: Key::Character("s".into()),
-        },
-        Message::SaveFile,
-    );
-
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::CTRL,
-            key: Key::Character("w".into()),
-        },
-        Message::TabCloseCurrent,
-    );
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::ALT,
-            key: Key::Character("1".into()),
-        },
-        Message::TabSelected(0),
-    );
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::ALT,
-            key: Key::Character("2".into()),
-        },
-        Message::TabSelected(1),
-    );
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::ALT,
-            key: Key::Character("3".into()),
-        },
-        Message::TabSelected(2),
-    );
-    key_bind.insert(
-        KeyBind {
-            modifiers: Modifiers::CTRL,
-            key: Key::Character("f".into()),
-        },
-        Message::TabSearchOpen,
-    );
-
-    key_bind
+impl KeyBind {
+    // characters are kept lowercase, shift is in the modifiers. a symbol has no
+    // case, shift already picked it, like ? for shift+/, so shift is dropped
+    pub fn new(mut modifiers: Modifiers, key: Key) -> Self {
+        let key = match key {
+            Key::Character(c) => {
+                if !has_case(&c) {
+                    modifiers.remove(Modifiers::SHIFT);
+                }
+                Key::Character(c.to_lowercase().into())
+            }
+            key => key,
+        };
+        Self { modifiers, key }
+    }
+
+    // like ctrl+shift+p, cmd is ctrl or the command key on macOS
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = text.split('+').collect();
+        // "ctrl++" binds the plus key
+        if text.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let Some((key, modifiers)) = parts.split_last() else {
+            return Err(format!("empty key in {}", text));
+        };
+
+        let mut bits = Modifiers::empty();
+        for modifier in modifiers {
+            bits |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "alt" | "option" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                "super" | "logo" | "meta" | "win" => Modifiers::LOGO,
+                "cmd" | "command" => Modifiers::COMMAND,
+                _ => return Err(format!("unknown modifier {} in {}", modifier, text)),
+            };
+        }
+        let key = parse_key(key).ok_or_else(|| format!("unknown key {} in {}", key, text))?;
+        // which symbol shift types depends on the layout, ctrl+shift+/ never arrives
+        if bits.shift()
+            && let Key::Character(c) = &key
+            && !has_case(c)
+        {
+            return Err(format!(
+                "shift with {} types another symbol, bind that one, like ctrl+? for ctrl+shift+/",
+                c
+            ));
+        }
+        Ok(Self::new(bits, key))
+    }
+
+    // shift, ctrl and the like only make up other keys
+    fn is_modifier(&self) -> bool {
+        matches!(
+            self.key,
+            Key::Named(
+                Named::Shift
+                    | Named::Control
+                    | Named::Alt
+                    | Named::AltGraph
+                    | Named::Super
+                    | Named::Meta
+                    | Named::Hyper
+            )
+        )
+    }
+}
+
+// letters, as opposed to symbols and digits
+fn has_case(text: &str) -> bool {
+    text.to_lowercase() != text.to_uppercase()
+}
+
+fn parse_key(text: &str) -> Option<Key> {
+    let lower = text.to_lowercase();
+    let named = match lower.as_str() {
+        "escape" | "esc" => Named::Escape,
+        "enter" | "return" => Named::Enter,
+        "tab" => Named::Tab,
+        "space" => Named::Space,
+        "backspace" => Named::Backspace,
+        "delete" | "del" => Named::Delete,
+        "insert" | "ins" => Named::Insert,
+        "left" | "arrowleft" => Named::ArrowLeft,
+        "right" | "arrowright" => Named::ArrowRight,
+        "up" | "arrowup" => Named::ArrowUp,
+        "down" | "arrowdown" => Named::ArrowDown,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "menu" | "contextmenu" => Named::ContextMenu,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ => {
+            let mut chars = lower.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Key::Character(c.to_string().into())),
+                _ => None,
+            };
+        }
+    };
+    Some(Key::Named(named))
+}
+
+// as shown next to a command, like Ctrl+Shift+P
+impl fmt::Display for KeyBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (Modifiers::CTRL, "Ctrl"),
+            (Modifiers::ALT, "Alt"),
+            (Modifiers::SHIFT, "Shift"),
+            (Modifiers::LOGO, "Super"),
+        ];
+        for (modifier, name) in modifiers {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match &self.key {
+            Key::Character(c) => write!(f, "{}", c.to_uppercase()),
+            Key::Named(named) => write!(f, "{:?}", named),
+            Key::Unidentified => write!(f, "?"),
+        }
+    }
+}
+
+// keys pressed one after another, like ctrl+k ctrl+c
+pub type Chord = Vec<KeyBind>;
+
+fn parse_chord(text: &str) -> Result<Chord, String> {
+    let chord: Chord = text
+        .split_whitespace()
+        .map(KeyBind::parse)
+        .collect::<Result<_, _>>()?;
+    if chord.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(chord)
+}
+
+fn chord_text(chord: &[KeyBind]) -> String {
+    let keys: Vec<String> = chord.iter().map(KeyBind::to_string).collect();
+    keys.join(" ")
+}
+
+// how the text box takes keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum Profile {
+    #[default]
+    Standard,
+    // normal, insert and visual modes in front of the keymap
+    Vim,
+    // emacs keys over the default ones
+    Emacs,
+}
+
+// the emacs profile, read like a keymap file before the user's own.
+// ctrl+x only starts chords here
+const EMACS: &str = r#"
+"ctrl+f" = "editor.move_right"
+"ctrl+b" = "editor.move_left"
+"ctrl+n" = "editor.move_down"
+"ctrl+p" = "editor.move_up"
+"ctrl+a" = "editor.move_home"
+"ctrl+e" = "editor.move_end"
+"alt+f" = "editor.move_word_right"
+"alt+b" = "editor.move_word_left"
+"ctrl+v" = "editor.move_page_down"
+"alt+v" = "editor.move_page_up"
+"ctrl+d" = "editor.delete"
+"alt+d" = "editor.delete_word"
+"ctrl+k" = "editor.kill_line"
+"ctrl+w" = "editor.cut"
+"alt+w" = "editor.copy"
+"ctrl+y" = "editor.yank"
+"alt+y" = "editor.yank_pop"
+"ctrl+space" = "editor.set_mark"
+"ctrl+g" = "editor.escape"
+"ctrl+/" = "editor.undo"
+"ctrl+s" = "search.open"
+"alt+x" = "palette.open"
+"ctrl+x" = "none"
+"ctrl+x ctrl+s" = "file.save"
+"ctrl+x ctrl+w" = "file.save_as"
+"ctrl+x ctrl+f" = "file.open"
+"ctrl+x b" = "file.quick_open"
+"ctrl+x k" = "tab.close"
+"ctrl+x u" = "editor.undo"
+"#;
+
+pub enum Press {
+    Bound(Action),
+    // the start of a chord, more keys to come
+    Pending,
+    // a chord was started but went nowhere, the key is dropped
+    Aborted,
+    Unbound,
+}
+
+// the default keys of the commands with the user's keymap file over them
+pub struct Keymap {
+    pub profile: Profile,
+    bindings: HashMap<Chord, (&'static str, Action)>,
+    // the starts of the chords of more than one key
+    prefixes: HashSet<Chord>,
+    // keys of an unfinished chord
+    pending: Mutex<Chord>,
+}
+
+impl Keymap {
+    // also returns what was wrong with the keymap file, for the user to see
+    pub fn load(commands: &[Command], profile: Profile) -> (Self, Vec<String>) {
+        let mut bindings = HashMap::new();
+        for command in commands {
+            for key in &command.keys {
+                bindings.insert(vec![key.clone()], (command.id, command.action.clone()));
+            }
+        }
+
+        let mut problems = Vec::new();
+        if profile == Profile::Emacs {
+            read(EMACS, commands, &mut bindings, &mut problems, false);
+        }
+        if let Some(path) = path().filter(|path| path.exists()) {
+            match fs::read_to_string(&path) {
+                Ok(text) => read(&text, commands, &mut bindings, &mut problems, true),
+                Err(err) => problems.push(format!("could not read {}: {}", path.display(), err)),
+            }
+        }
+
+        let prefixes: HashSet<Chord> = bindings
+            .keys()
+            .flat_map(|chord| (1..chord.len()).map(|end| chord[..end].to_vec()))
+            .collect();
+        for prefix in &prefixes {
+            if let Some((id, _)) = bindings.get(prefix) {
+                problems.push(format!(
+                    "{} runs {} and starts longer chords, which can't be reached",
+                    chord_text(prefix),
+                    id
+                ));
+            }
+        }
+        problems.sort();
+
+        let keymap = Self {
+            profile,
+            bindings,
+            prefixes,
+            pending: Mutex::new(Vec::new()),
+        };
+        (keymap, problems)
+    }
+
+    pub fn press(&self, key: KeyBind) -> Press {
+        if key.is_modifier() {
+            return Press::Unbound;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(key);
+        if let Some((_, action)) = self.bindings.get(&*pending) {
+            pending.clear();
+            return Press::Bound(action.clone());
+        }
+        if self.prefixes.contains(&*pending) {
+            return Press::Pending;
+        }
+        let aborted = pending.len() > 1;
+        pending.clear();
+        if aborted {
+            Press::Aborted
+        } else {
+            Press::Unbound
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    // the keys of the unfinished chord, as shown
+    pub fn pending(&self) -> Option<String> {
+        let pending = self.pending.lock().unwrap();
+        (!pending.is_empty()).then(|| chord_text(&pending))
+    }
+
+    // every chord running the command, as shown
+    pub fn chords(&self, id: &str) -> Vec<String> {
+        let mut chords: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, (command, _))| *command == id)
+            .map(|(chord, _)| chord_text(chord))
+            .collect();
+        chords.sort();
+        chords
+    }
+}
+
+// lines of "keys" = "command id", "none" unbinds the keys:
+//   "ctrl+k ctrl+s" = "file.save"
+//   "ctrl+s" = "none"
+// symbols are written as typed, "ctrl+?" rather than "ctrl+shift+/"
+fn read(
+    text: &str,
+    commands: &[Command],
+    bindings: &mut HashMap<Chord, (&'static str, Action)>,
+    problems: &mut Vec<String>,
+    // a profile replaces keys on purpose, the user file may not
+    report_replaced: bool,
+) {
+    let table = match text.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            problems.push(format!("invalid keymap: {}", err));
+            return;
+        }
+    };
+
+    // the same keys written differently
+    let mut seen: HashMap<Chord, String> = HashMap::new();
+    for (keys, value) in table {
+        let Some(id) = value.as_str() else {
+            problems.push(format!("{}: expected a command name", keys));
+            continue;
+        };
+        let chord = match parse_chord(&keys) {
+            Ok(chord) => chord,
+            Err(err) => {
+                problems.push(format!("{}: {}", keys, err));
+                continue;
+            }
+        };
+        if let Some(other) = seen.insert(chord.clone(), id.to_string()) {
+            problems.push(format!("{} is bound to both {} and {}", keys, other, id));
+        }
+
+        if id == "none" {
+            bindings.remove(&chord);
+            continue;
+        }
+        match commands.iter().find(|command| command.id == id) {
+            Some(command) => {
+                if let Some((other, _)) =
+                    bindings.insert(chord, (command.id, command.action.clone()))
+                    && other != command.id
+                    && report_replaced
+                {
+                    problems.push(format!("{} runs {} instead of {}", keys, id, other));
+                }
+            }
+            None => problems.push(format!("{}: unknown command {}", keys, id)),
+        }
+    }
+}
+
+// ~/.config/editorium/keymap.toml on linux
+fn path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("editorium").join("keymap.toml"))
 }
@@ -1,6 +1,4 @@
 use std::{
-    cmp::Ordering,
-    collections::HashMap,
     fs,
     path::PathBuf,
     str::FromStr,
@@ -9,27 +7,54 @@ use std::{
 
 use clap::Parser;
 use iced::{
-    Element, Length, Subscription, Task,
-    advanced::graphics::core::keyboard,
+    Alignment, Element, Length, Size, Subscription, Task,
+    advanced::{
+        graphics::core::keyboard,
+        widget::{self, operate, operation::focusable},
+    },
     event, time,
-    widget::{Container, PaneGrid, button, column, pane_grid, pick_list, row, scrollable},
+    widget::{
+        Container, PaneGrid, button, center, column, opaque, pane_grid, pick_list, row, scrollable,
+        stack, text, text_input,
+    },
+    window,
 };
 use key_binds::KeyBind;
 use rfd::FileDialog;
 
 mod cli;
+mod commands;
+mod file;
 mod font;
+mod fuzzy;
+mod git;
+mod history;
 mod key_binds;
+mod matcher;
+mod palette;
 mod project;
+mod project_search;
+mod quick_open;
+mod search;
 mod tab;
 mod text_box;
 mod theme;
+mod watcher;
 
 // TODO move
 static FONT_SYSTEM: OnceLock<RwLock<cosmic_text::FontSystem>> = OnceLock::new();
 static SYNTAX_SYSTEM: OnceLock<cosmic_text::SyntaxSystem> = OnceLock::new();
 static SWASH_CACHE: OnceLock<RwLock<cosmic_text::SwashCache>> = OnceLock::new();
-static KEY_BINDINGS: OnceLock<HashMap<KeyBind, Message>> = OnceLock::new();
+static COMMANDS: OnceLock<Vec<commands::Command>> = OnceLock::new();
+static KEYMAP: OnceLock<key_binds::Keymap> = OnceLock::new();
+
+fn commands() -> &'static [commands::Command] {
+    COMMANDS.get().unwrap()
+}
+
+fn keymap() -> &'static key_binds::Keymap {
+    KEYMAP.get().unwrap()
+}
 
 fn font_system() -> &'static RwLock<cosmic_text::FontSystem> {
     FONT_SYSTEM.get().unwrap()
@@ -42,34 +67,116 @@ fn swash_cache() -> &'static RwLock<cosmic_text::SwashCache> {
 #[derive(Debug, Clone)]
 enum Message {
     KeyPressed(keyboard::Modifiers, keyboard::Key),
+    ModifiersChanged(keyboard::Modifiers),
     OpenFileSelector,
     OpenDirectorySelector,
-    OpenFile(PathBuf),
+    // optionally moves the cursor, e.g. to a search result
+    OpenFile(PathBuf, Option<cosmic_text::Cursor>),
+    // moves the cursor to a line and a char column, counted from 0
+    OpenFileAt(PathBuf, usize, usize),
     OpenProject(PathBuf),
     TabSelected(usize),
+    TabNext,
+    TabPrevious,
     TabClose(usize),
+    WindowCloseRequested(window::Id),
+    CloseDialog(CloseChoice),
     TabCloseCurrent,
+    // closes the active tab without asking, unsaved changes are lost
+    TabDiscardCurrent,
     TabSearch(String),
     TabSearchOpen,
     TabSearchClose,
+    TabSearchNext,
+    TabSearchPrevious,
+    TabSearchSubmit,
+    TabSearchToggle(search::Toggle),
+    TabReplace(String),
+    TabReplaceNext,
+    TabReplaceAll,
+    TabEdited,
+    // vim took a key, its status line is shown again
+    VimStatus,
+    ProjectSearchOpen,
+    ProjectSearchQuery(String),
+    ProjectSearchInclude(String),
+    ProjectSearchExclude(String),
+    ProjectSearchToggle(search::Toggle),
+    ProjectSearchRun,
+    ProjectSearchDone(usize, Result<project_search::Results, String>),
+    ProjectSearchReplace(String),
+    ProjectSearchToggleHit(usize, usize),
+    ProjectSearchToggleFile(usize),
+    ProjectSearchReplaceAll,
+    ProjectSearchReplaceDone(Result<(), String>),
+    QuickOpenShow,
+    QuickOpenQuery(String),
+    // files of a project walk, by generation
+    QuickOpenIndexed(usize, Vec<quick_open::Candidate>),
+    QuickOpenSelect(usize),
+    QuickOpenSubmit,
+    PaletteShow,
+    PaletteQuery(String),
+    PaletteSelect(usize),
+    PaletteSubmit,
+    // closes quick open or the palette, whichever is open
+    PickerClose,
     PaneResized(pane_grid::ResizeEvent),
+    WindowResized(Size),
     ProjectTreeSelect(usize),
+    ProjectTreePress(usize),
+    ProjectTreeRelease(usize),
+    ProjectTreeHover(Option<usize>),
+    // lists hidden and ignored entries too
+    ProjectTreeToggleHidden,
+    // entries of a directory, read in the background
+    ProjectTreeLoaded(PathBuf, Result<Vec<project::Entry>, String>),
+    ProjectTreeCollapseAll,
+    // status of a git read, by generation
+    ProjectTreeGitStatus(usize, Result<git::Status, String>),
+    // opens the actions of a node
+    ProjectTreeMenu(usize),
+    ProjectTreeMenuToggle,
+    ProjectTreeAction(usize, project::NodeAction),
+    ProjectTreeEditInput(String),
+    ProjectTreeEditSubmit,
+    ProjectTreeEditCancel,
+    // opens the tree to the file of the active tab
+    ProjectTreeRevealActive,
+    ProjectTreeToggleFollow,
+    // moves the keys between the project tree and the editor
+    ProjectTreeFocusToggle,
+    EditorFocused,
+    NewFile,
     SaveFile,
+    SaveFileAs,
+    DismissError,
+    FileChanged(PathBuf),
+    // paths under the project changed on disk
+    ProjectTreeChanged(Vec<PathBuf>),
+    ProjectTreeScrolled(scrollable::Viewport),
+    TabConflictReload,
+    TabConflictKeep,
+    TabConflictDiff,
     AutoScroll,
     SetAutoScroll(Option<f32>),
+    // the keys of an unfinished chord changed
+    Chord(Option<String>),
 }
 
 fn main() -> Result<(), iced::Error> {
     FONT_SYSTEM.get_or_init(|| RwLock::new(cosmic_text::FontSystem::new()));
     SYNTAX_SYSTEM.get_or_init(|| cosmic_text::SyntaxSystem::new());
     SWASH_CACHE.get_or_init(|| RwLock::new(cosmic_text::SwashCache::new()));
-    KEY_BINDINGS.get_or_init(|| key_binds::default());
+    COMMANDS.get_or_init(commands::registry);
 
     // use editorium=debug to get only this crate
     env_logger::init();
     iced::application("Editorium", App::update, App::view)
         .subscription(App::subscription)
         .theme(App::theme)
+        // histories are stored before closing
+        .exit_on_close_request(false)
         .settings(iced::Settings {
             fonts: font::load(),
             ..Default::default()
@@ -85,6 +192,7 @@ struct Pane {
 enum PaneType {
     FileTree,
     Editor,
+    SearchResults,
 }
 
 impl Pane {
@@ -95,12 +203,39 @@ impl Pane {
     }
 }
 
+// answer to the unsaved changes dialog
+#[derive(Debug, Clone, Copy)]
+enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+// what waits for the unsaved changes dialog
+enum Closing {
+    Tab(usize),
+    Window(window::Id),
+}
+
 struct App {
     tabs: tab::TabView,
     project_tree: project::ProjectTree,
+    project_search: project_search::ProjectSearch,
+    quick_open: quick_open::QuickOpen,
+    palette: palette::Palette,
     current_project: Option<project::Project>,
     panes: pane_grid::State<Pane>,
     auto_scroll: Option<f32>,
+    modifiers: keyboard::Modifiers,
+    closing: Option<Closing>,
+    // the project tree is never taller, lines for it cover the tree's viewport
+    window_height: f32,
+    // why saving from the close dialog failed, the dialog covers the error bar
+    close_error: Option<String>,
+    // shown above the editor until dismissed
+    error: Option<String>,
+    // keys of an unfinished chord, like Ctrl+K
+    chord: Option<String>,
 }
 
 fn create_pane() -> pane_grid::State<Pane> {
@@ -112,6 +247,16 @@ fn create_pane() -> pane_grid::State<Pane> {
 
     pane_grid_state.resize(split, 0.2);
 
+    let (_, split) = pane_grid_state
+        .split(
+            pane_grid::Axis::Horizontal,
+            pane,
+            Pane::new(PaneType::SearchResults),
+        )
+        .unwrap();
+
+    pane_grid_state.resize(split, 0.6);
+
     pane_grid_state
 }
 
@@ -119,67 +264,206 @@ impl App {
     fn new() -> (Self, Task<Message>) {
         let cli = cli::Cli::parse();
 
-        KEY_BINDINGS.get_or_init(|| key_binds::default());
+        COMMANDS.get_or_init(commands::registry);
+        let (keymap, problems) = key_binds::Keymap::load(commands(), cli.profile);
+        KEYMAP.get_or_init(|| keymap);
+        for problem in &problems {
+            log::warn!("keymap: {}", problem);
+        }
 
         let mut app = Self {
-            tabs: tab::TabView::new(),
+            tabs: tab::TabView::new(cli.undo_limit, cli.backup),
             project_tree: project::ProjectTree::new(),
+            project_search: project_search::ProjectSearch::new(),
+            quick_open: quick_open::QuickOpen::new(),
+            palette: palette::Palette::new(),
             current_project: None,
             panes: create_pane(),
             auto_scroll: None,
+            modifiers: keyboard::Modifiers::default(),
+            closing: None,
+            window_height: window::Settings::default().size.height,
+            close_error: None,
+            error: (!problems.is_empty())
+                .then(|| format!("Problems in the keymap: {}", problems.join("; "))),
+            chord: None,
         };
 
+        let mut task = Task::none();
         if let Some(path) = cli.path {
             if path.is_dir() {
-                app.open_project(path);
+                task = app.open_project(path);
             } else {
-                app.open_file(path);
+                app.open_file(path, None);
             }
         }
 
-        (app, Task::none())
+        (app, task)
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
+        let task = self.handle(message);
+        Task::batch([task, self.active_changed()])
+    }
+
+    // the tree marks the file of the active tab, and opens to it when following
+    fn active_changed(&mut self) -> Task<Message> {
+        let path = self.tabs.active_path();
+        if path == self.project_tree.active {
+            return Task::none();
+        }
+        self.project_tree.active = path.clone();
+        match path {
+            Some(path) if self.project_tree.follow => self.project_tree.reveal(path),
+            _ => Task::none(),
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::OpenFileSelector => {
                 if let Some(file_path) =
                     select_file(&self.current_project.as_ref().map(|p| p.path.clone()))
                 {
-                    self.open_file(file_path)
+                    self.open_file(file_path, None)
                 }
             }
             Message::OpenDirectorySelector => {
                 if let Some(dir_path) =
                     select_dir(&self.current_project.as_ref().map(|p| p.path.clone()))
                 {
-                    self.open_project(dir_path)
+                    return self.open_project(dir_path);
                 }
             }
             Message::TabSelected(tab) => {
                 self.tabs.activate(tab);
                 self.redraw_active_editor();
             }
-            Message::OpenProject(project) => self.open_project(project),
-            Message::OpenFile(file_path) => self.open_file(file_path),
+            Message::TabNext => {
+                self.tabs.cycle(1);
+                self.redraw_active_editor();
+            }
+            Message::TabPrevious => {
+                self.tabs.cycle(-1);
+                self.redraw_active_editor();
+            }
+            Message::OpenProject(project) => return self.open_project(project),
+            Message::OpenFile(file_path, cursor) => self.open_file(file_path, cursor),
+            Message::OpenFileAt(file_path, line, column) => {
+                if let Some(index) = self.open_tab(file_path) {
+                    self.tabs.tab_mut(index).unwrap().goto_column(line, column);
+                    self.redraw_active_editor();
+                }
+            }
+            Message::NewFile => {
+                if let Ok(index) = self.tabs.insert(None) {
+                    self.tabs.activate(index);
+                    self.redraw_active_editor();
+                }
+            }
             Message::SaveFile => {
+                if let Some(active) = self.tabs.active()
+                    && let Err(err) = self.save_tab(active, false)
+                {
+                    self.error = Some(err);
+                }
+            }
+            Message::SaveFileAs => {
+                if let Some(active) = self.tabs.active()
+                    && let Err(err) = self.save_tab(active, true)
+                {
+                    self.error = Some(err);
+                }
+            }
+            Message::DismissError => self.error = None,
+            Message::FileChanged(path) => {
+                if let Some(index) = self.tabs.position(path)
+                    && let Some(tab) = self.tabs.tab_mut(index)
+                    && let Err(err) = tab.file_changed()
+                {
+                    log::error!("could not reload file: {}", err);
+                    self.error = Some(format!("Could not reload {}: {}", tab.get_name(), err));
+                }
+            }
+            Message::TabConflictReload | Message::TabConflictKeep | Message::TabConflictDiff => {
                 if let Some(active) = self.tabs.active() {
                     let tab = self.tabs.tab_mut(active).unwrap();
-                    match tab.save() {
-                        Ok(_) => {}
-                        Err(err) => log::error!("could not open directory: {}", err),
+                    let result = match message {
+                        Message::TabConflictReload => tab.reload(),
+                        Message::TabConflictKeep => tab.keep_mine(),
+                        _ => tab.toggle_conflict_diff(),
+                    };
+                    if let Err(err) = result {
+                        log::error!("could not read file: {}", err);
+                        self.error = Some(format!("Could not read {}: {}", tab.get_name(), err));
                     }
-                };
+                }
             }
             Message::TabCloseCurrent => {
+                if let Some(active) = self.tabs.active() {
+                    self.close_tab(active);
+                }
+            }
+            Message::TabDiscardCurrent => {
                 if let Some(active) = self.tabs.active() {
                     self.tabs.remove(active);
                     self.redraw_active_editor();
                 }
             }
-            Message::TabClose(tab) => {
-                self.tabs.remove(tab);
-                self.redraw_active_editor();
+            Message::TabClose(tab) => self.close_tab(tab),
+            Message::WindowCloseRequested(id) => {
+                if self.tabs.dirty().is_empty() {
+                    self.tabs.store_histories();
+                    return window::close(id);
+                }
+                self.closing = Some(Closing::Window(id));
+            }
+            Message::CloseDialog(choice) => {
+                let Some(closing) = self.closing.take() else {
+                    return Task::none();
+                };
+                self.close_error = None;
+                match choice {
+                    CloseChoice::Save => {
+                        let dirty: Vec<usize> = match closing {
+                            Closing::Tab(index) => vec![index],
+                            Closing::Window(_) => self
+                                .tabs
+                                .dirty()
+                                .into_iter()
+                                .map(|(index, _)| index)
+                                .collect(),
+                        };
+                        let errors: Vec<String> = dirty
+                            .into_iter()
+                            .filter_map(|index| self.save_tab(index, false).err())
+                            .collect();
+                        if !errors.is_empty() {
+                            self.close_error = Some(errors.join("\n"));
+                        }
+
+                        // keep asking while something could not be saved
+                        let saved = match closing {
+                            Closing::Tab(index) => !self.tabs.is_dirty(index),
+                            Closing::Window(_) => self.tabs.dirty().is_empty(),
+                        };
+                        self.closing = Some(closing);
+                        if saved {
+                            return self.update(Message::CloseDialog(CloseChoice::Discard));
+                        }
+                    }
+                    CloseChoice::Discard => match closing {
+                        Closing::Tab(index) => {
+                            self.tabs.remove(index);
+                            self.redraw_active_editor();
+                        }
+                        Closing::Window(id) => {
+                            self.tabs.store_histories();
+                            return window::close(id);
+                        }
+                    },
+                    CloseChoice::Cancel => {}
+                }
             }
             Message::TabSearch(text) => {
                 if let Some(active) = self.tabs.active() {
@@ -188,6 +472,7 @@ impl App {
                 }
             }
             Message::TabSearchOpen => {
+                self.project_tree.blur();
                 if let Some(active) = self.tabs.active() {
                     let tab = self.tabs.tab_mut(active).unwrap();
                     return tab.search_open(None);
@@ -199,50 +484,265 @@ impl App {
                     return tab.search_close();
                 }
             }
+            Message::TabSearchNext => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.search_next();
+                }
+            }
+            Message::TabSearchPrevious => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.search_previous();
+                }
+            }
+            Message::TabSearchSubmit => {
+                // text_input submits on enter regardless of shift
+                if self.modifiers.shift() {
+                    return self.update(Message::TabSearchPrevious);
+                }
+                return self.update(Message::TabSearchNext);
+            }
+            Message::TabSearchToggle(toggle) => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.search_toggle(toggle);
+                }
+            }
+            Message::TabReplace(text) => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.replace_text(text);
+                }
+            }
+            Message::TabReplaceNext => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.replace_next();
+                }
+            }
+            Message::TabReplaceAll => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.replace_all();
+                }
+            }
+            Message::TabEdited => {
+                if let Some(active) = self.tabs.active() {
+                    let tab = self.tabs.tab_mut(active).unwrap();
+                    tab.on_edit();
+                }
+            }
+            Message::ProjectSearchOpen => {
+                self.project_tree.blur();
+                return self.project_search.focus();
+            }
+            Message::PaletteShow => {
+                self.project_tree.blur();
+                self.quick_open.close();
+                return self.palette.show(commands());
+            }
+            Message::PaletteQuery(query) => return self.palette.set_query(query, commands()),
+            Message::PaletteSelect(index) => {
+                self.palette.select(index);
+                return self.update(Message::PaletteSubmit);
+            }
+            Message::PaletteSubmit => {
+                if let Some(command) = self.palette.choice(commands()) {
+                    self.palette.close();
+                    // the command may move the focus on
+                    let focus = self.tabs.focus();
+                    return focus.chain(self.update(command));
+                }
+            }
+            Message::PickerClose => {
+                self.quick_open.close();
+                self.palette.close();
+                return self.tabs.focus();
+            }
+            Message::QuickOpenShow => {
+                self.project_tree.blur();
+                self.palette.close();
+                return self.quick_open.show(self.project_tree.walk());
+            }
+            Message::QuickOpenQuery(query) => return self.quick_open.set_query(query),
+            Message::QuickOpenIndexed(generation, files) => {
+                self.quick_open.indexed(generation, files)
+            }
+            Message::QuickOpenSelect(index) => {
+                self.quick_open.select(index);
+                return self.update(Message::QuickOpenSubmit);
+            }
+            Message::QuickOpenSubmit => {
+                if let Some(open) = self.quick_open.choice() {
+                    self.quick_open.close();
+                    let task = self.update(open);
+                    return Task::batch([task, self.tabs.focus()]);
+                }
+            }
+            Message::ProjectSearchQuery(query) => self.project_search.set_query(query),
+            Message::ProjectSearchInclude(include) => self.project_search.set_include(include),
+            Message::ProjectSearchExclude(exclude) => self.project_search.set_exclude(exclude),
+            Message::ProjectSearchToggle(toggle) => {
+                self.project_search.toggle(toggle);
+                return self.update(Message::ProjectSearchRun);
+            }
+            Message::ProjectSearchRun => {
+                let walk = self.project_tree.walk();
+                return self.project_search.run(walk, self.tabs.texts());
+            }
+            Message::ProjectSearchDone(generation, results) => {
+                self.project_search.done(generation, results)
+            }
+            Message::ProjectSearchReplace(replace) => self.project_search.set_replace(replace),
+            Message::ProjectSearchToggleHit(file, hit) => self.project_search.toggle_hit(file, hit),
+            Message::ProjectSearchToggleFile(file) => self.project_search.toggle_file(file),
+            Message::ProjectSearchReplaceAll => {
+                let mut on_disk = Vec::new();
+                let mut skipped = 0;
+                for (path, replacements) in self.project_search.replacements() {
+                    // open files are edited in their tab, so the edit can be undone
+                    if let Some(index) = self.tabs.position(path.clone()) {
+                        let tab = self.tabs.tab_mut(index).unwrap();
+                        skipped += tab.replace(&replacements);
+                    } else {
+                        on_disk.push((path, replacements));
+                    }
+                }
+                self.redraw_active_editor();
+                return self.project_search.replace_on_disk(on_disk, skipped);
+            }
+            Message::ProjectSearchReplaceDone(result) => {
+                // searching again clears errors, so the replace error is set after
+                let task = self.update(Message::ProjectSearchRun);
+                self.project_search.replace_done(result);
+                return task;
+            }
+            Message::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            Message::Chord(chord) => self.chord = chord,
+            Message::KeyPressed(_, _) if self.closing.is_some() => {
+                // the dialog takes no shortcuts
+            }
             Message::KeyPressed(modifier, key) => {
-                if let Some(value) = KEY_BINDINGS.get().unwrap().get(&key_binds::KeyBind {
-                    modifiers: modifier,
-                    key: key,
-                }) {
-                    return self.update(value.clone());
+                if self.quick_open.open {
+                    if let Some(task) = self.quick_open.key_press(&key) {
+                        return task;
+                    }
+                } else if self.palette.open {
+                    if let Some(task) = self.palette.key_press(&key) {
+                        return task;
+                    }
+                } else if let Some(task) = self.project_tree.key_press(modifier, &key) {
+                    return task;
+                }
+                let press = keymap().press(KeyBind::new(modifier, key));
+                self.chord = keymap().pending();
+                if let key_binds::Press::Bound(commands::Action::App(message)) = press {
+                    return self.update(message);
                 }
             }
             Message::PaneResized(pane_grid::ResizeEvent { split, ratio }) => {
                 self.panes.resize(split, ratio);
             }
+            Message::WindowResized(size) => self.window_height = size.height,
             Message::ProjectTreeSelect(id) => {
-                let node = if let Some(node) = self.project_tree.node_mut(id) {
-                    if let project::NodeKind::Directory { open } = &mut node.kind {
-                        *open = !*open;
-                    }
-                    Some(node.clone())
-                } else {
-                    None
+                let Some(node) = self.project_tree.node(id) else {
+                    return Task::none();
                 };
-
-                let pos = self.project_tree.position(id).unwrap();
-
-                if let Some(node) = node {
-                    match node.kind {
-                        project::NodeKind::Directory { open } => {
-                            if open {
-                                self.tree_open_dir(node.path, pos, node.indent + 1);
-                            } else {
-                                while let Some(child) = self.project_tree.node_at(pos + 1) {
-                                    if child.indent > node.indent {
-                                        self.project_tree.remove(child.id);
-                                    } else {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        project::NodeKind::File => {
-                            self.open_file(node.path);
-                        }
+                match node.kind {
+                    project::NodeKind::Directory { .. } => return self.project_tree.toggle(id),
+                    project::NodeKind::File => self.open_file(node.path.clone(), None),
+                }
+            }
+            Message::ProjectTreeLoaded(dir, result) => {
+                return self.project_tree.loaded(dir, result);
+            }
+            Message::ProjectTreeGitStatus(generation, result) => {
+                return self.project_tree.git_loaded(generation, result);
+            }
+            Message::ProjectTreeCollapseAll => self.project_tree.collapse_all(),
+            Message::ProjectTreeChanged(paths) => return self.project_tree.changed(paths),
+            Message::ProjectTreeScrolled(viewport) => self.project_tree.scrolled(viewport),
+            Message::ProjectTreePress(id) => {
+                self.project_tree.pressed = Some(id);
+                self.project_tree.selected = Some(id);
+                self.project_tree.focused = true;
+                self.project_tree.menu = false;
+            }
+            Message::ProjectTreeRelease(id) => match self.project_tree.pressed.take() {
+                Some(pressed) if pressed == id => {
+                    return self.update(Message::ProjectTreeSelect(id));
+                }
+                Some(pressed) => {
+                    // dropped onto a directory, or next to a file
+                    let from = self
+                        .project_tree
+                        .node(pressed)
+                        .map(|node| node.path.clone());
+                    let to = self
+                        .project_tree
+                        .dir_of(id)
+                        .and_then(|dir| self.project_tree.node(dir))
+                        .map(|dir| dir.path.clone());
+                    if let (Some(from), Some(to)) = (from, to)
+                        && let Some(name) = from.file_name()
+                        && from.parent() != Some(to.as_path())
+                    {
+                        let to = to.join(name);
+                        return self.tree_move(from, to);
                     }
                 }
+                None => {}
+            },
+            Message::ProjectTreeHover(id) => self.project_tree.hovered = id,
+            Message::ProjectTreeToggleHidden => {
+                self.project_tree.show_hidden = !self.project_tree.show_hidden;
+                return self.project_tree.refresh_all();
             }
+            Message::ProjectTreeMenu(id) => {
+                self.project_tree.selected = Some(id);
+                self.project_tree.menu = true;
+            }
+            Message::ProjectTreeMenuToggle => {
+                if self.project_tree.selected.is_some() {
+                    self.project_tree.menu = !self.project_tree.menu;
+                }
+            }
+            Message::ProjectTreeAction(id, action) => {
+                self.project_tree.menu = false;
+                return self.tree_action(id, action);
+            }
+            Message::ProjectTreeEditInput(text) => {
+                if let Some(edit) = &mut self.project_tree.edit {
+                    edit.text = text;
+                }
+            }
+            Message::ProjectTreeEditSubmit => return self.tree_edit_submit(),
+            Message::ProjectTreeEditCancel => self.project_tree.edit = None,
+            Message::ProjectTreeRevealActive => {
+                if let Some(path) = self.tabs.active_path() {
+                    return self.project_tree.reveal(path);
+                }
+            }
+            Message::ProjectTreeToggleFollow => {
+                self.project_tree.follow = !self.project_tree.follow;
+                if self.project_tree.follow
+                    && let Some(path) = self.tabs.active_path()
+                {
+                    return self.project_tree.reveal(path);
+                }
+            }
+            Message::ProjectTreeFocusToggle => {
+                if self.project_tree.focused {
+                    self.project_tree.blur();
+                    return self.tabs.focus();
+                }
+                // no widget has the id, so every one lets go of the focus
+                let unfocus = operate(focusable::focus(widget::Id::unique()));
+                return Task::batch([unfocus, self.project_tree.focus()]);
+            }
+            Message::EditorFocused => self.project_tree.blur(),
+            Message::VimStatus => {}
             Message::AutoScroll => {
                 if let Some(auto_scroll) = self.auto_scroll {
                     if let Some(active) = self.tabs.active() {
@@ -301,23 +801,55 @@ impl App {
             button("Open Dir").on_press(Message::OpenDirectorySelector) //     // current_project
                                                                         //     // current git branch
                                                                         //     // run
-        ];
+        ]
+        .push_maybe(self.chord.as_ref().map(|chord| {
+            text(format!(
+                "{} was pressed, waiting for the next key...",
+                chord
+            ))
+            .style(theme::text::dimmed)
+        }))
+        .align_y(Alignment::Center)
+        .spacing(8.0);
 
-        let pane_grid = PaneGrid::new(&self.panes, |_, state, _| {
-            if state.pane_type == PaneType::Editor {
-                pane_grid::Content::new(self.tabs.view())
-            } else {
-                let file_tree = self.project_tree.view();
+        let pane_grid = PaneGrid::new(&self.panes, |_, state, _| match state.pane_type {
+            PaneType::Editor => pane_grid::Content::new(self.tabs.view()),
+            PaneType::SearchResults => pane_grid::Content::new(self.project_search.view()),
+            PaneType::FileTree => {
+                let toolbar = row![
+                    search::toggle_button(
+                        "Hidden",
+                        self.project_tree.show_hidden,
+                        Message::ProjectTreeToggleHidden,
+                    ),
+                    search::toggle_button(
+                        "Follow",
+                        self.project_tree.follow,
+                        Message::ProjectTreeToggleFollow,
+                    ),
+                    button("Reveal").on_press(Message::ProjectTreeRevealActive),
+                    button("Collapse").on_press(Message::ProjectTreeCollapseAll),
+                ]
+                .push_maybe(
+                    (!self.project_tree.filter.is_empty())
+                        .then(|| text(format!("Filter: {}", self.project_tree.filter))),
+                )
+                .spacing(4)
+                .align_y(Alignment::Center);
 
-                pane_grid::Content::new(
-                    scrollable(Container::new(file_tree))
+                pane_grid::Content::new(column![
+                    toolbar,
+                    // the tree builds the lines that fit in the window's height
+                    scrollable(self.project_tree.view(self.window_height))
+                        .id(self.project_tree.scroll_id())
                         .width(Length::Fill)
                         .height(Length::Fill)
                         .direction(scrollable::Direction::Both {
                             vertical: scrollable::Scrollbar::default().scroller_width(0).width(0),
                             horizontal: scrollable::Scrollbar::default().scroller_width(0).width(0),
-                        }),
-                )
+                        })
+                        .on_scroll(Message::ProjectTreeScrolled),
+                ])
             }
         })
         .width(Length::Fill)
@@ -325,10 +857,107 @@ impl App {
         .spacing(10)
         .on_resize(10, Message::PaneResized);
 
-        let content: Element<Message, theme::MyTheme> = column![nav_bar, pane_grid].into();
+        let error_bar = self.error.as_ref().map(|error| {
+            Container::new(
+                row![
+                    text(error).width(Length::Fill),
+                    button("Dismiss").on_press(Message::DismissError),
+                ]
+                .align_y(Alignment::Center),
+            )
+            .padding(5)
+            .style(theme::container::error)
+        });
+
+        let content: Element<Message, theme::MyTheme> = column![nav_bar]
+            .push_maybe(error_bar)
+            .push(pane_grid)
+            .into();
 
         // content.explain(iced::Color::from_rgb(1.0, 0.0, 0.0))
-        content
+        let content = if self.quick_open.open {
+            stack![content, self.quick_open.view()].into()
+        } else if self.palette.open {
+            stack![content, self.palette.view(commands())].into()
+        } else {
+            content
+        };
+        if let Some(closing) = &self.closing {
+            stack![content, opaque(self.close_dialog(closing))].into()
+        } else {
+            content
+        }
+    }
+
+    fn close_dialog(&self, closing: &Closing) -> Element<'_, Message, theme::MyTheme> {
+        let names: Vec<String> = match closing {
+            Closing::Tab(index) => self
+                .tabs
+                .dirty()
+                .into_iter()
+                .filter(|(dirty, _)| dirty == index)
+                .map(|(_, name)| name)
+                .collect(),
+            Closing::Window(_) => self
+                .tabs
+                .dirty()
+                .into_iter()
+                .map(|(_, name)| name)
+                .collect(),
+        };
+
+        let dialog = Container::new(
+            column![text("Save changes before closing?"), text(names.join("\n")),]
+                .push_maybe(self.close_error.as_ref().map(|error| {
+                    Container::new(text(error))
+                        .padding(5)
+                        .style(theme::container::error)
+                }))
+                .push(
+                    row![
+                        button("Save").on_press(Message::CloseDialog(CloseChoice::Save)),
+                        button("Discard").on_press(Message::CloseDialog(CloseChoice::Discard)),
+                        button("Cancel").on_press(Message::CloseDialog(CloseChoice::Cancel)),
+                    ]
+                    .spacing(10),
+                )
+                .spacing(10),
+        )
+        .padding(20)
+        .style(theme::container::dialog);
+
+        center(dialog).style(theme::container::backdrop).into()
+    }
+
+    // untitled tabs, and every tab when choosing, ask where to save. the error is
+    // for the caller to show, the close dialog shows it in place
+    fn save_tab(&mut self, index: usize, choose_path: bool) -> Result<(), String> {
+        let working_dir = self.current_project.as_ref().map(|p| p.path.clone());
+        let Some(tab) = self.tabs.tab_mut(index) else {
+            return Ok(());
+        };
+
+        let result = if choose_path || tab.file_path.is_none() {
+            match select_save_file(&working_dir, &tab.get_name()) {
+                Some(path) => tab.save_as(path),
+                None => return Ok(()),
+            }
+        } else {
+            tab.save()
+        };
+        result.map_err(|err| {
+            log::error!("could not save file: {}", err);
+            format!("Could not save {}: {}", tab.get_name(), err)
+        })
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        if self.tabs.is_dirty(index) {
+            self.closing = Some(Closing::Tab(index));
+            return;
+        }
+        self.tabs.remove(index);
+        self.redraw_active_editor();
     }
 
     // note: events seem to call on everybody's on_event, with subscription last
@@ -340,9 +969,20 @@ impl App {
                     event::Status::Ignored => Some(Message::KeyPressed(modifiers, key)),
                 }
             }
+            event::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                Some(Message::ModifiersChanged(modifiers))
+            }
             _ => None,
         })];
 
+        if self.quick_open.open || self.palette.open {
+            subscriptions.push(quick_open::escape());
+        }
+        subscriptions.push(window::close_requests().map(Message::WindowCloseRequested));
+        subscriptions.push(window::resize_events().map(|(_, size)| Message::WindowResized(size)));
+        subscriptions.push(watcher::files(self.tabs.paths()));
+        subscriptions.push(watcher::directories(self.project_tree.watched()));
+
         if let Some(_) = self.auto_scroll {
             subscriptions
                 .push(time::every(time::Duration::from_millis(10)).map(|_| Message::AutoScroll));
@@ -356,29 +996,42 @@ impl App {
         theme::MyTheme::default()
     }
 
-    fn open_project(&mut self, path: PathBuf) {
+    fn open_project(&mut self, path: PathBuf) -> Task<Message> {
         let path = fs::canonicalize(&path).expect("could not canonicalize");
         self.current_project = Some(project::Project::new(path.clone()));
-        self.project_tree.clear();
-        self.project_tree.insert(path, 0, 0);
+        self.project_tree.open(path)
     }
 
-    fn open_file(&mut self, file_path: PathBuf) {
-        let file_path = fs::canonicalize(&file_path).expect("could not canonicalize");
-        if let Some(pos) = self.tabs.position(file_path.clone()) {
-            self.tabs.activate(pos);
-            self.redraw_active_editor();
+    fn open_file(&mut self, file_path: PathBuf, cursor: Option<cosmic_text::Cursor>) {
+        let Some(index) = self.open_tab(file_path) else {
             return;
+        };
+        if let Some(cursor) = cursor {
+            self.tabs.tab_mut(index).unwrap().goto(cursor);
         }
-        let index = match self.tabs.insert(Some(file_path)) {
+        self.redraw_active_editor()
+    }
+
+    // activates the tab of the file, opening it if needed. the path may be gone
+    // since it was listed, like in quick open
+    fn open_tab(&mut self, file_path: PathBuf) -> Option<usize> {
+        let opened = fs::canonicalize(&file_path).and_then(|file_path| {
+            match self.tabs.position(file_path.clone()) {
+                Some(pos) => Ok((pos, file_path)),
+                None => Ok((self.tabs.insert(Some(file_path.clone()))?, file_path)),
+            }
+        });
+        let (index, file_path) = match opened {
             Ok(ok) => ok,
             Err(err) => {
                 log::error!("could not open file: {}", err);
-                return;
+                self.error = Some(format!("Could not open {}: {}", file_path.display(), err));
+                return None;
             }
         };
         self.tabs.activate(index);
-        self.redraw_active_editor()
+        self.quick_open.opened(&file_path);
+        Some(index)
     }
 
     fn redraw_active_editor(&mut self) {
@@ -388,46 +1041,169 @@ impl App {
         }
     }
 
-    fn tree_open_dir(&mut self, path: PathBuf, pos: usize, indent: usize) {
-        let path = fs::canonicalize(&path).expect("could not canonicalize");
+    fn tree_action(&mut self, id: usize, action: project::NodeAction) -> Task<Message> {
+        let Some(node) = self.project_tree.node(id) else {
+            return Task::none();
+        };
+        let path = node.path.clone();
+        let name = node.name.clone();
 
-        let read_dir = match fs::read_dir(path) {
-            Ok(ok) => ok,
-            Err(err) => {
-                log::error!("could not open directory: {}", err);
-                return;
+        match action {
+            project::NodeAction::NewFile | project::NodeAction::NewFolder => {
+                let Some(dir) = self.project_tree.dir_of(id) else {
+                    return Task::none();
+                };
+                // the name is typed at the top of the open directory
+                let open = match self.project_tree.node(dir) {
+                    Some(node) if !self.project_tree.is_open(&node.path) => {
+                        self.project_tree.toggle(dir)
+                    }
+                    _ => Task::none(),
+                };
+                let target = if action == project::NodeAction::NewFile {
+                    project::EditTarget::NewFile(dir)
+                } else {
+                    project::EditTarget::NewFolder(dir)
+                };
+                let edit = self.tree_edit(project::Edit::new(target, String::new()));
+                return Task::batch([open, edit]);
             }
-        };
-        let mut nodes = Vec::new();
-        for dir_entry in read_dir {
-            match dir_entry {
-                Ok(ok) => {
-                    nodes.push(ok.path());
+            project::NodeAction::Rename => {
+                let edit = project::Edit::new(project::EditTarget::Rename(id), name);
+                return self.tree_edit(edit);
+            }
+            project::NodeAction::Duplicate | project::NodeAction::Delete => {
+                let result = if action == project::NodeAction::Duplicate {
+                    file::duplicate(&path).map(|_| ())
+                } else {
+                    file::trash(&path)
+                };
+                match result {
+                    Ok(_) => {
+                        let parent = path.parent().into_iter().map(PathBuf::from).collect();
+                        return self.project_tree.refresh(parent);
+                    }
+                    Err(err) => {
+                        let verb = if action == project::NodeAction::Duplicate {
+                            "duplicate"
+                        } else {
+                            "delete"
+                        };
+                        self.fail(format!("Could not {} {}", verb, name), err);
+                    }
                 }
-                Err(err) => {
-                    log::error!("could not read directory entry: {}", err);
-                    return;
+            }
+            project::NodeAction::CopyPath => {
+                return iced::clipboard::write(path.display().to_string());
+            }
+            project::NodeAction::Reveal => {
+                if let Err(err) = file::reveal(&path) {
+                    self.fail(format!("Could not reveal {}", name), err);
                 }
             }
         }
-        nodes.sort_by(|a, b| {
-            if a.is_dir() && !b.is_dir() {
-                return Ordering::Less;
+        Task::none()
+    }
+
+    fn tree_edit(&mut self, edit: project::Edit) -> Task<Message> {
+        let id = edit.id.clone();
+        self.project_tree.edit = Some(edit);
+        text_input::focus(id)
+    }
+
+    fn tree_edit_submit(&mut self) -> Task<Message> {
+        // an invalid name leaves the input open to correct it
+        let Some(edit) = &self.project_tree.edit else {
+            return Task::none();
+        };
+        let name = edit.text.trim();
+        if name.is_empty() || name == "." || name == ".." || name.contains(std::path::is_separator)
+        {
+            self.error = Some(format!("Invalid name: {:?}", name));
+            return Task::none();
+        }
+        let Some(edit) = self.project_tree.edit.take() else {
+            return Task::none();
+        };
+        let name = edit.text.trim();
+
+        match edit.target {
+            project::EditTarget::NewFile(dir) | project::EditTarget::NewFolder(dir) => {
+                let Some(dir) = self.project_tree.node(dir).map(|node| node.path.clone()) else {
+                    return Task::none();
+                };
+                let path = dir.join(name);
+                let result = if let project::EditTarget::NewFile(_) = edit.target {
+                    fs::File::create_new(&path).map(|_| ())
+                } else {
+                    fs::create_dir(&path)
+                };
+                match result {
+                    Ok(_) => {
+                        if let project::EditTarget::NewFile(_) = edit.target {
+                            self.open_file(path.clone(), None);
+                        }
+                        let refresh = self.project_tree.refresh(vec![dir]);
+                        Task::batch([refresh, self.project_tree.reveal(path)])
+                    }
+                    Err(err) => {
+                        self.fail(format!("Could not create {}", name), err);
+                        Task::none()
+                    }
+                }
             }
-            if b.is_dir() && !a.is_dir() {
-                return Ordering::Greater;
+            project::EditTarget::Rename(id) => {
+                let Some(from) = self.project_tree.node(id).map(|node| node.path.clone()) else {
+                    return Task::none();
+                };
+                let to = from.with_file_name(name);
+                if to == from {
+                    return Task::none();
+                }
+                self.tree_move(from, to)
             }
-            a.cmp(b)
-        });
+        }
+    }
 
-        let mut position = pos + 1;
-        for node in nodes {
-            self.project_tree.insert(node, position, indent);
-            position += 1;
+    // renames or moves a file or directory, open tabs follow it
+    fn tree_move(&mut self, from: PathBuf, to: PathBuf) -> Task<Message> {
+        let name = from
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        if to.exists() {
+            self.error = Some(format!("Could not move {}: {} exists", name, to.display()));
+            return Task::none();
         }
+        if let Err(err) = fs::rename(&from, &to) {
+            self.fail(format!("Could not move {}", name), err);
+            return Task::none();
+        }
+
+        self.tabs.rename_path(&from, &to);
+        let dirs = [from.parent(), to.parent()];
+        let refresh = self
+            .project_tree
+            .refresh(dirs.into_iter().flatten().map(PathBuf::from).collect());
+        Task::batch([refresh, self.project_tree.reveal(to)])
+    }
+
+    fn fail(&mut self, what: String, err: impl std::fmt::Display) {
+        log::error!("{}: {}", what, err);
+        self.error = Some(format!("{}: {}", what, err));
     }
 }
 
+// runs blocking work, like walking a directory, off the iced executor
+async fn background<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    let (sender, receiver) = iced::futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+    receiver.await.expect("background work panicked")
+}
+
 fn select_dir(working_dir: &Option<PathBuf>) -> Option<PathBuf> {
     let mut dialog = FileDialog::new().set_title("Open a directory...");
 
@@ -453,3 +1229,15 @@ fn select_file(working_dir: &Option<PathBuf>) -> Option<PathBuf> {
     }
     return None;
 }
+
+fn select_save_file(working_dir: &Option<PathBuf>, name: &str) -> Option<PathBuf> {
+    let mut dialog = FileDialog::new()
+        .set_title("Save file as...")
+        .set_file_name(name);
+
+    if let Some(working_dir) = working_dir {
+        dialog = dialog.set_directory(working_dir)
+    }
+
+    dialog.save_file()
+}
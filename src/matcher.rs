@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    Match, WalkBuilder,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+
+// the ignore rules of a project: .gitignore and .ignore files in every directory,
+// .git/info/exclude and the global git excludes
+pub struct Matcher {
+    root: PathBuf,
+    global: Gitignore,
+    // rules of each directory, read when first needed
+    dirs: Mutex<HashMap<PathBuf, Gitignore>>,
+}
+
+impl Matcher {
+    pub fn new(root: PathBuf) -> Self {
+        let (global, err) = Gitignore::global();
+        if let Some(err) = err {
+            log::warn!("could not read global git excludes: {}", err);
+        }
+        Self {
+            root,
+            global,
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // forgets the rules read so far, an ignore file may have changed
+    pub fn reload(&self) {
+        self.dirs.lock().unwrap().clear();
+    }
+
+    // path is ignored by its own rules or by those of a directory it is in
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // the root itself is never ignored
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        // deeper rules win
+        let mut dirs = self.dirs.lock().unwrap();
+        for dir in path.ancestors().skip(1) {
+            let rules = dirs
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| self.read_rules(dir));
+            match rules.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+            if dir == self.root {
+                break;
+            }
+        }
+        self.global
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    }
+
+    // a dotfile, or inside a dot directory of the project
+    pub fn is_hidden(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        relative
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+    }
+
+    fn read_rules(&self, dir: &Path) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut files = vec![dir.join(".gitignore"), dir.join(".ignore")];
+        if dir == self.root {
+            files.insert(0, dir.join(".git").join("info").join("exclude"));
+        }
+        for file in files.into_iter().filter(|file| file.is_file()) {
+            if let Some(err) = builder.add(&file) {
+                log::warn!("could not read {}: {}", file.display(), err);
+            }
+        }
+        builder.build().unwrap_or_else(|err| {
+            log::warn!("invalid ignore rules in {}: {}", dir.display(), err);
+            Gitignore::empty()
+        })
+    }
+}
+
+// a file Matcher reads rules from, its rules are read again when it changes
+pub fn is_rules_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    matches!(name, Some(".gitignore" | ".ignore")) || path.ends_with(".git/info/exclude")
+}
+
+// a walk of the project. dotfiles follow the tree's toggle, ignored entries are
+// build output and the like, left out unless asked for
+#[derive(Clone)]
+pub struct Walk {
+    pub matcher: Arc<Matcher>,
+    pub show_hidden: bool,
+    pub show_ignored: bool,
+}
+
+impl Walk {
+    pub fn root(&self) -> &Path {
+        &self.matcher.root
+    }
+
+    pub fn build(&self) -> WalkBuilder {
+        self.build_with(|_| false)
+    }
+
+    // ignored entries an include glob names by its path, like target/** does target,
+    // are walked too. globs naming no directory, like *.rs, leave them out
+    pub fn build_including(&self, include: &[String]) -> WalkBuilder {
+        let bases: Vec<String> = include.iter().filter_map(|glob| glob_base(glob)).collect();
+        self.build_with(move |relative| {
+            bases.iter().any(|base| {
+                relative == base
+                    || base
+                        .strip_prefix(relative.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+                    || relative
+                        .strip_prefix(base.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        })
+    }
+
+    fn build_with(&self, named: impl Fn(&String) -> bool + Send + Sync + 'static) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(&self.matcher.root);
+        // the Matcher decides, not the walker's own rules
+        builder.standard_filters(false);
+        let walk = self.clone();
+        builder.filter_entry(move |entry| {
+            let path = entry.path();
+            // git's own files are not project files, even when hidden ones are shown
+            if path == walk.matcher.root.join(".git") {
+                return false;
+            }
+            if !walk.show_hidden && walk.matcher.is_hidden(path) {
+                return false;
+            }
+            let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+            walk.show_ignored
+                || !walk.matcher.is_ignored(path, is_dir)
+                || path.strip_prefix(&walk.matcher.root).is_ok_and(|relative| {
+                    let relative: Vec<String> = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                        .collect();
+                    named(&relative.join("/"))
+                })
+        });
+        builder
+    }
+}
+
+// the directories a glob is rooted in: target for target/**, none for *.rs
+fn glob_base(glob: &str) -> Option<String> {
+    let glob = glob.trim_start_matches('/');
+    let base = match glob.find(['*', '?', '[', '{']) {
+        Some(meta) => glob[..meta].rfind('/').map_or("", |slash| &glob[..slash]),
+        None => glob.trim_end_matches('/'),
+    };
+    (!base.is_empty()).then(|| base.to_string())
+}
@@ -0,0 +1,126 @@
+use iced::{
+    Element, Length, Task,
+    keyboard::Key,
+    widget::{self, column, row, text, text_input},
+};
+
+use crate::{
+    Message,
+    commands::{Action, Command},
+    fuzzy, keymap,
+    quick_open::{self, Picker},
+    theme,
+};
+
+const MAX_RECENT: usize = 16;
+
+// runs any command of the registry by name
+pub struct Palette {
+    pub open: bool,
+    input_id: text_input::Id,
+    query: String,
+    // ids of the commands run, most recent first
+    recent: Vec<&'static str>,
+    // indices into the registry
+    picker: Picker,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input_id: text_input::Id::unique(),
+            query: String::new(),
+            recent: Vec::new(),
+            picker: Picker::new(),
+        }
+    }
+
+    pub fn show(&mut self, commands: &[Command]) -> Task<Message> {
+        self.open = true;
+        self.query.clear();
+        self.rank(commands);
+        widget::text_input::focus(self.input_id.clone())
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn set_query(&mut self, query: String, commands: &[Command]) -> Task<Message> {
+        self.query = query;
+        self.rank(commands);
+        self.picker.scroll_to_selected()
+    }
+
+    pub fn key_press(&mut self, key: &Key) -> Option<Task<Message>> {
+        self.picker.key_press(key)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.picker.select(index);
+    }
+
+    // the selected command, remembered as recently used
+    pub fn choice(&mut self, commands: &[Command]) -> Option<Message> {
+        let command = &commands[self.picker.choice()?];
+        self.recent.retain(|id| *id != command.id);
+        self.recent.insert(0, command.id);
+        self.recent.truncate(MAX_RECENT);
+        match &command.action {
+            Action::App(message) => Some(message.clone()),
+            Action::Editor(_) => None,
+        }
+    }
+
+    // recently used commands first, then the best matches, then registry order.
+    // editor commands only run from keys, the editor has no focus under the palette
+    fn rank(&mut self, commands: &[Command]) {
+        let mut ranked: Vec<(usize, i64, usize)> = commands
+            .iter()
+            .enumerate()
+            .filter(|(_, command)| matches!(command.action, Action::App(_)))
+            .filter_map(|(index, command)| {
+                let score = fuzzy::score(&self.query, command.title)?;
+                let recent = self
+                    .recent
+                    .iter()
+                    .position(|id| *id == command.id)
+                    .unwrap_or(MAX_RECENT);
+                Some((recent, -score, index))
+            })
+            .collect();
+        ranked.sort();
+        self.picker
+            .set_results(ranked.into_iter().map(|(_, _, index)| index).collect());
+    }
+
+    pub fn view<'a>(&'a self, commands: &'a [Command]) -> Element<'a, Message, theme::MyTheme> {
+        let input = text_input("Run a command", &self.query)
+            .id(self.input_id.clone())
+            .on_input(Message::PaletteQuery)
+            .on_submit(Message::PaletteSubmit);
+
+        let list = self.picker.view(
+            |index| {
+                let command = &commands[index];
+                let keys = keymap().chords(command.id);
+                row![
+                    text(command.title).width(Length::Fill),
+                    text(keys.join(", ")).style(theme::text::dimmed),
+                ]
+                .into()
+            },
+            Message::PaletteSelect,
+        );
+
+        let content = column![input, list]
+            .push_maybe(
+                self.picker
+                    .is_empty()
+                    .then(|| text("No matching commands").style(theme::text::dimmed)),
+            )
+            .spacing(8.0);
+        quick_open::overlay(content)
+    }
+}
@@ -1,11 +1,30 @@
-use std::{cmp::min, collections::HashMap, fmt, io, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt, fs, io, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use iced::{
-    Element, Padding,
-    widget::{Column, Row, button, text},
+    Element, Padding, Task,
+    keyboard::{Key, Modifiers, key::Named},
+    widget::{
+        Column, Row, Space, button, container, mouse_area,
+        scrollable::{self, AbsoluteOffset, Viewport},
+        text, text_input,
+    },
+};
+
+use crate::{
+    Message, background, font, fuzzy, git,
+    matcher::{self, Matcher},
+    theme,
 };
 
-use crate::{Message, font, theme};
+// every line of the tree is this tall, so the lines in view follow from the scroll
+// offset and only those are built
+const ROW_HEIGHT: f32 = 32.0;
 
 #[derive()]
 pub enum Error {
@@ -55,126 +74,972 @@ impl fmt::Display for Project {
 }
 
 pub struct ProjectTree {
-    order: Vec<usize>,
-    items: HashMap<usize, Node>,
+    root: Option<usize>,
+    nodes: HashMap<usize, Node>,
+    // every node by its path
+    paths: HashMap<PathBuf, usize>,
     next_id: usize,
+    pub selected: Option<usize>,
+    pub hovered: Option<usize>,
+    // node the mouse went down on, dropped onto the node it goes up on
+    pub pressed: Option<usize>,
+    // actions of the selected node are shown
+    pub menu: bool,
+    pub edit: Option<Edit>,
+    // hidden and ignored entries are listed, dimmed
+    pub show_hidden: bool,
+    // keys move the selection and type into the filter
+    pub focused: bool,
+    pub filter: String,
+    // file of the active tab, marked in the tree
+    pub active: Option<PathBuf>,
+    // the tree opens to the active file whenever it changes
+    pub follow: bool,
+    // nodes left by the filter: matches and the directories they are in
+    filtered: Option<HashSet<usize>>,
+    // directories the filter opened, closed again once it is cleared
+    filter_opened: HashSet<usize>,
+    matcher: Option<Arc<Matcher>>,
+    // colors nodes, read with the entries
+    git: git::Status,
+    // bumped on every git read, results of older reads are dropped
+    git_generation: usize,
+    // a read is running, changes meanwhile read once more after it
+    git_reading: bool,
+    git_again: bool,
+    // path to select once the directories leading to it are loaded
+    revealing: Option<PathBuf>,
+    // directories being read, loaded ones too when read again
+    reading: HashSet<PathBuf>,
+    scroll_id: scrollable::Id,
+    // vertical scroll offset and height of the viewport, as last reported
+    offset: f32,
+    height: f32,
 }
 
 impl ProjectTree {
     pub fn new() -> Self {
         Self {
-            order: Vec::new(),
-            items: HashMap::new(),
+            root: None,
+            nodes: HashMap::new(),
+            paths: HashMap::new(),
             next_id: 0,
+            selected: None,
+            hovered: None,
+            pressed: None,
+            menu: false,
+            edit: None,
+            show_hidden: false,
+            focused: false,
+            filter: String::new(),
+            filtered: None,
+            filter_opened: HashSet::new(),
+            active: None,
+            follow: false,
+            matcher: None,
+            git: git::Status::default(),
+            git_generation: 0,
+            git_reading: false,
+            git_again: false,
+            revealing: None,
+            reading: HashSet::new(),
+            scroll_id: scrollable::Id::unique(),
+            offset: 0.0,
+            height: 0.0,
         }
     }
 
+    // walks the project's files with the tree's dotfile toggle, None before a project
+    // is open. ignored files are never walked, the toggle shows them to manage them
+    pub fn walk(&self) -> Option<matcher::Walk> {
+        Some(matcher::Walk {
+            matcher: self.matcher.clone()?,
+            show_hidden: self.show_hidden,
+            show_ignored: false,
+        })
+    }
+
+    pub fn open(&mut self, root: PathBuf) -> Task<Message> {
+        self.clear();
+        self.matcher = Some(Arc::new(Matcher::new(root.clone())));
+        let entry = Entry {
+            is_dir: true,
+            dimmed: false,
+            path: root,
+        };
+        self.root = Some(self.insert(entry, None));
+        self.read_git()
+    }
+
     pub fn clear(&mut self) {
-        self.order.clear();
-        self.items.clear();
+        self.root = None;
+        self.nodes.clear();
+        self.paths.clear();
         self.next_id = 0;
+        self.selected = None;
+        self.hovered = None;
+        self.pressed = None;
+        self.menu = false;
+        self.edit = None;
+        self.filter.clear();
+        self.filtered = None;
+        self.filter_opened.clear();
+        self.matcher = None;
+        self.git = git::Status::default();
+        self.git_generation += 1;
+        self.git_reading = false;
+        self.git_again = false;
+        self.revealing = None;
+        self.reading.clear();
     }
 
-    pub fn insert(&mut self, path: PathBuf, pos: usize, indent: usize) {
-        let pos = min(pos, self.order.len());
-        self.order.insert(pos, self.next_id);
-        self.items
-            .insert(self.next_id, Node::new(path, self.next_id, indent));
+    fn insert(&mut self, entry: Entry, parent: Option<usize>) -> usize {
+        let id = self.next_id;
         self.next_id += 1;
+        let depth = parent.map_or(0, |parent| self.nodes[&parent].depth + 1);
+        self.paths.insert(entry.path.clone(), id);
+        self.nodes.insert(id, Node::new(entry, id, parent, depth));
+        id
+    }
+
+    // drops a node and everything below it
+    fn remove(&mut self, id: usize) {
+        let Some(node) = self.nodes.remove(&id) else {
+            return;
+        };
+        self.paths.remove(&node.path);
+        for state in [&mut self.selected, &mut self.hovered, &mut self.pressed] {
+            if *state == Some(id) {
+                *state = None;
+            }
+        }
+        if let NodeKind::Directory {
+            children: Children::Loaded(children),
+            ..
+        } = node.kind
+        {
+            for child in children {
+                self.remove(child);
+            }
+        }
+    }
+
+    pub fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.paths.get(path).copied()
+    }
+
+    // the node itself if it is a directory, otherwise its parent
+    pub fn dir_of(&self, id: usize) -> Option<usize> {
+        let node = self.nodes.get(&id)?;
+        match node.kind {
+            NodeKind::Directory { .. } => Some(id),
+            NodeKind::File => node.parent,
+        }
     }
 
-    pub fn remove(&mut self, id: usize) {
-        self.items.remove(&id);
-        if let Some(pos) = self.position(id) {
-            self.order.remove(pos);
+    // directory is shown with its children
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.find(path)
+            .and_then(|id| self.nodes.get(&id))
+            .is_some_and(|node| matches!(node.kind, NodeKind::Directory { open: true, .. }))
+    }
+
+    pub fn scroll_id(&self) -> scrollable::Id {
+        self.scroll_id.clone()
+    }
+
+    pub fn scrolled(&mut self, viewport: Viewport) {
+        self.offset = viewport.absolute_offset().y;
+        self.height = viewport.bounds().height;
+    }
+
+    // opens or closes a directory, its entries are read the first time it opens
+    pub fn toggle(&mut self, id: usize) -> Task<Message> {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return Task::none();
+        };
+        let NodeKind::Directory { open, children } = &mut node.kind else {
+            return Task::none();
+        };
+        *open = !*open;
+        // opened or closed by hand, it stays that way
+        self.filter_opened.remove(&id);
+        if *open && matches!(children, Children::Unloaded) {
+            *children = Children::Loading;
+            return self.load(self.nodes[&id].path.clone());
         }
+        Task::none()
     }
 
-    pub fn node_mut(&mut self, id: usize) -> Option<&mut Node> {
-        self.items.get_mut(&id)
+    // closes every directory but the root, they keep their entries
+    pub fn collapse_all(&mut self) {
+        for node in self.nodes.values_mut() {
+            if Some(node.id) != self.root
+                && let NodeKind::Directory { open, .. } = &mut node.kind
+            {
+                *open = false;
+            }
+        }
     }
 
-    pub fn node_at(&self, pos: usize) -> Option<&Node> {
-        let id = self.order.get(pos)?;
-        self.items.get(id)
+    // reads a directory off the ui thread
+    fn load(&mut self, dir: PathBuf) -> Task<Message> {
+        self.reading.insert(dir.clone());
+        let matcher = self.matcher.clone();
+        let show_hidden = self.show_hidden;
+        let path = dir.clone();
+        Task::perform(
+            background(move || list(&path, matcher.as_deref(), show_hidden)),
+            move |result| {
+                Message::ProjectTreeLoaded(dir.clone(), result.map_err(|err| err.to_string()))
+            },
+        )
     }
-    pub fn position(&self, id: usize) -> Option<usize> {
-        return self.order.iter().position(|x| *x == id);
+
+    // entries of a directory arrived. children that are still there keep their state
+    // and their own children
+    pub fn loaded(&mut self, dir: PathBuf, result: Result<Vec<Entry>, String>) -> Task<Message> {
+        self.reading.remove(&dir);
+        let Some(id) = self.find(&dir) else {
+            return Task::none();
+        };
+        let entries = match result {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::error!("could not open directory {}: {}", dir.display(), err);
+                Vec::new()
+            }
+        };
+
+        let old = match &self.nodes[&id].kind {
+            NodeKind::Directory {
+                children: Children::Loaded(children),
+                ..
+            } => children.iter().copied().collect(),
+            _ => HashSet::new(),
+        };
+        let mut children = Vec::with_capacity(entries.len());
+        let mut kept = HashSet::new();
+        for entry in entries {
+            let child = match self.find(&entry.path) {
+                Some(child) if old.contains(&child) => {
+                    let node = self.nodes.get_mut(&child).unwrap();
+                    node.dimmed = entry.dimmed;
+                    kept.insert(child);
+                    child
+                }
+                _ => self.insert(entry, Some(id)),
+            };
+            children.push(child);
+        }
+        for child in old.difference(&kept) {
+            self.remove(*child);
+        }
+        if let NodeKind::Directory { children: slot, .. } =
+            &mut self.nodes.get_mut(&id).unwrap().kind
+        {
+            *slot = Children::Loaded(children);
+        }
+        if self.filtered.is_some() {
+            self.apply_filter();
+        }
+
+        self.continue_reveal()
     }
 
-    pub fn view(&self) -> Column<Message, theme::MyTheme> {
-        let nodes: Vec<Element<Message, theme::MyTheme>> =
-            self.order
-                .iter()
-                .map(|id| {
-                    let node = self.items.get(id).unwrap();
-                    let elem = match &node.kind {
-                        NodeKind::File => button(text(&node.name))
-                            .on_press(Message::OpenFile(node.path.to_owned())),
-                        NodeKind::Directory { open, .. } => {
-                            let icon = if *open {
-                                font::caret_down()
-                            } else {
-                                font::caret_right()
-                            };
-                            button(
-                                Row::new()
-                                    .push(text(icon).font(font::ICON_SOLID).width(15.0))
-                                    .push(text(&node.name))
-                                    .spacing(4.0),
-                            )
-                            .on_press(Message::ProjectTreeSelect(node.id))
+    // reads directories again after their entries changed, ones never opened are read
+    // when they are
+    pub fn refresh(&mut self, dirs: Vec<PathBuf>) -> Task<Message> {
+        // writes into unlisted directories, like a build's, leave the status be
+        let git_dir = self.root.map(|root| self.nodes[&root].path.join(".git"));
+        let in_git = dirs.iter().any(|dir| {
+            git_dir
+                .as_ref()
+                .is_some_and(|git_dir| dir.starts_with(git_dir))
+        });
+        let dirs: Vec<PathBuf> = dirs.into_iter().filter(|dir| self.is_loaded(dir)).collect();
+        let git = if in_git || !dirs.is_empty() {
+            self.read_git()
+        } else {
+            Task::none()
+        };
+        let loads: Vec<Task<Message>> = dirs.into_iter().map(|dir| self.load(dir)).collect();
+        Task::batch(loads.into_iter().chain([git]))
+    }
+
+    // paths changed by other programs. a changed ignore file changes what every
+    // directory lists
+    pub fn changed(&mut self, paths: Vec<PathBuf>) -> Task<Message> {
+        if paths.iter().any(|path| matcher::is_rules_file(path)) {
+            if let Some(matcher) = &self.matcher {
+                matcher.reload();
+            }
+            return self.refresh_all();
+        }
+        let dirs: HashSet<PathBuf> = paths
+            .iter()
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+        self.refresh(dirs.into_iter().collect())
+    }
+
+    // directories for the watcher: the ones read, which are the ones listed, and
+    // those of git's own files, sorted to restart the watcher only when they change
+    pub fn watched(&self) -> Vec<PathBuf> {
+        let Some(root) = self.root.map(|root| &self.nodes[&root].path) else {
+            return Vec::new();
+        };
+        let git_dir = root.join(".git");
+        let mut dirs: Vec<PathBuf> = self
+            .paths
+            .keys()
+            .filter(|path| self.is_loaded(path))
+            .cloned()
+            .chain(
+                [git_dir.join("info"), git_dir]
+                    .into_iter()
+                    .filter(|dir| dir.is_dir()),
+            )
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    fn is_loaded(&self, dir: &Path) -> bool {
+        self.find(dir).is_some_and(|id| {
+            matches!(
+                self.nodes[&id].kind,
+                NodeKind::Directory {
+                    children: Children::Loaded(_),
+                    ..
+                }
+            )
+        })
+    }
+
+    // one read at a time, changes during it are read after it in one go
+    fn read_git(&mut self) -> Task<Message> {
+        let Some(root) = self.root.map(|root| self.nodes[&root].path.clone()) else {
+            return Task::none();
+        };
+        if self.git_reading {
+            self.git_again = true;
+            return Task::none();
+        }
+        self.git_reading = true;
+        self.git_generation += 1;
+        let generation = self.git_generation;
+        Task::perform(
+            background(move || git::Status::read(&root)),
+            move |result| {
+                Message::ProjectTreeGitStatus(generation, result.map_err(|err| err.to_string()))
+            },
+        )
+    }
+
+    pub fn git_loaded(
+        &mut self,
+        generation: usize,
+        result: Result<git::Status, String>,
+    ) -> Task<Message> {
+        if generation != self.git_generation {
+            return Task::none();
+        }
+        self.git_reading = false;
+        match result {
+            Ok(git) => self.git = git,
+            Err(err) => log::warn!("could not read git status: {}", err),
+        }
+        if mem::take(&mut self.git_again) {
+            return self.read_git();
+        }
+        Task::none()
+    }
+
+    // every directory read so far, after the listing rules changed
+    pub fn refresh_all(&mut self) -> Task<Message> {
+        self.refresh(self.paths.keys().cloned().collect())
+    }
+
+    // opens the directories leading to path, then selects and scrolls to it
+    pub fn reveal(&mut self, path: PathBuf) -> Task<Message> {
+        self.revealing = Some(path);
+        self.continue_reveal()
+    }
+
+    fn continue_reveal(&mut self) -> Task<Message> {
+        let (Some(target), Some(root)) = (self.revealing.clone(), self.root) else {
+            return Task::none();
+        };
+        let Ok(relative) = target.strip_prefix(&self.nodes[&root].path) else {
+            self.revealing = None;
+            return Task::none();
+        };
+
+        let mut current = root;
+        for component in relative.components() {
+            let path = self.nodes[&current].path.join(component);
+            let reading = self.reading.contains(&self.nodes[&current].path);
+            let node = self.nodes.get_mut(&current).unwrap();
+            let NodeKind::Directory { open, children } = &mut node.kind else {
+                self.revealing = None;
+                return Task::none();
+            };
+            *open = true;
+            match children {
+                Children::Unloaded => {
+                    *children = Children::Loading;
+                    let dir = node.path.clone();
+                    return self.load(dir);
+                }
+                // continues when the entries arrive
+                Children::Loading => return Task::none(),
+                Children::Loaded(_) => match self.find(&path) {
+                    Some(child) => current = child,
+                    // new entries show up once the directory is read again
+                    None if reading => return Task::none(),
+                    // not listed, like an ignored file
+                    None => {
+                        self.revealing = None;
+                        return Task::none();
+                    }
+                },
+            }
+        }
+
+        self.revealing = None;
+        self.selected = Some(current);
+        self.scroll_to_selected()
+    }
+
+    // scrolls the selected node into view, to the middle when it is out of view
+    fn scroll_to_selected(&self) -> Task<Message> {
+        let Some(selected) = self.selected else {
+            return Task::none();
+        };
+        let Some(line) = self
+            .lines()
+            .iter()
+            .position(|line| *line == Line::Node(selected))
+        else {
+            return Task::none();
+        };
+        let top = line as f32 * ROW_HEIGHT;
+        if top >= self.offset && top + ROW_HEIGHT <= self.offset + self.height {
+            return Task::none();
+        }
+        // to the middle of the viewport
+        let y = (top - (self.height - ROW_HEIGHT) / 2.0).max(0.0);
+        scrollable::scroll_to(self.scroll_id.clone(), AbsoluteOffset { x: 0.0, y })
+    }
+
+    // shown nodes, top to bottom
+    fn rows(&self) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            rows.push(id);
+            if let NodeKind::Directory {
+                open: true,
+                children: Children::Loaded(children),
+            } = &self.nodes[&id].kind
+            {
+                stack.extend(children.iter().rev().filter(|child| {
+                    self.filtered
+                        .as_ref()
+                        .is_none_or(|filtered| filtered.contains(child))
+                }));
+            }
+        }
+        rows
+    }
+
+    // the tree takes the keys, the widget that had them lets go of them elsewhere
+    pub fn focus(&mut self) -> Task<Message> {
+        self.focused = true;
+        if self.selected.is_none_or(|id| !self.rows().contains(&id)) {
+            self.selected = self.root;
+        }
+        self.scroll_to_selected()
+    }
+
+    pub fn blur(&mut self) {
+        self.focused = false;
+        self.menu = false;
+        self.set_filter(String::new());
+    }
+
+    // keys of the focused tree. None when the tree has no use for the key
+    pub fn key_press(&mut self, modifiers: Modifiers, key: &Key) -> Option<Task<Message>> {
+        if !self.focused {
+            return None;
+        }
+        if modifiers.control() || modifiers.alt() || modifiers.logo() {
+            return None;
+        }
+
+        match key {
+            Key::Named(Named::ArrowUp) => self.step(-1),
+            Key::Named(Named::ArrowDown) => self.step(1),
+            Key::Named(Named::Home) => self.step(isize::MIN),
+            Key::Named(Named::End) => self.step(isize::MAX),
+            Key::Named(Named::ArrowLeft) => {
+                let id = self.selected?;
+                let node = &self.nodes[&id];
+                match (&node.kind, node.parent) {
+                    (NodeKind::Directory { open: true, .. }, _) => return Some(self.toggle(id)),
+                    (_, Some(parent)) => self.selected = Some(parent),
+                    _ => {}
+                }
+                Some(self.scroll_to_selected())
+            }
+            Key::Named(Named::ArrowRight) => {
+                let id = self.selected?;
+                match self.nodes[&id].kind {
+                    NodeKind::Directory { open: false, .. } => Some(self.toggle(id)),
+                    // into the first entry
+                    NodeKind::Directory { open: true, .. } => self.step(1),
+                    NodeKind::File => Some(Task::none()),
+                }
+            }
+            Key::Named(Named::Enter) => {
+                let id = self.selected?;
+                Some(Task::done(Message::ProjectTreeSelect(id)))
+            }
+            Key::Named(Named::Escape) => {
+                if self.menu {
+                    self.menu = false;
+                } else if !self.filter.is_empty() {
+                    self.set_filter(String::new());
+                } else {
+                    return None;
+                }
+                Some(self.scroll_to_selected())
+            }
+            Key::Named(Named::Backspace) => {
+                let mut filter = self.filter.clone();
+                filter.pop();
+                self.set_filter(filter);
+                Some(self.scroll_to_selected())
+            }
+            Key::Character(c) => {
+                self.set_filter(format!("{}{}", self.filter, c));
+                Some(self.scroll_to_selected())
+            }
+            _ => None,
+        }
+    }
+
+    // moves the selection over the shown nodes, clamped at both ends
+    fn step(&mut self, delta: isize) -> Option<Task<Message>> {
+        let rows = self.rows();
+        let current = self
+            .selected
+            .and_then(|id| rows.iter().position(|row| *row == id));
+        let index = match current {
+            Some(current) => current.saturating_add_signed(delta),
+            None => 0,
+        };
+        self.selected = rows.get(index.min(rows.len().saturating_sub(1))).copied();
+        self.menu = false;
+        Some(self.scroll_to_selected())
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        if let Some(best) = self.apply_filter() {
+            self.selected = Some(best);
+        }
+    }
+
+    // keeps the nodes read so far whose name matches the filter and opens the
+    // directories they are in. returns the best match, the upper one of equals
+    fn apply_filter(&mut self) -> Option<usize> {
+        if self.filter.is_empty() {
+            self.filtered = None;
+            self.close_filter_opened();
+            return None;
+        }
+
+        let scores: HashMap<usize, i64> = self
+            .nodes
+            .values()
+            .filter_map(|node| Some((node.id, fuzzy::score(&self.filter, &node.name)?)))
+            .collect();
+        let mut filtered: HashSet<usize> = scores.keys().copied().collect();
+        for &id in scores.keys() {
+            let mut parent = self.nodes[&id].parent;
+            while let Some(id) = parent {
+                let added = filtered.insert(id);
+                if let NodeKind::Directory { open, .. } = &mut self.nodes.get_mut(&id).unwrap().kind
+                    && !*open
+                {
+                    *open = true;
+                    self.filter_opened.insert(id);
+                }
+                // already there with its parents
+                if !added {
+                    break;
+                }
+                parent = self.nodes[&id].parent;
+            }
+        }
+        self.filtered = Some(filtered);
+
+        let mut best: Option<(i64, usize)> = None;
+        for id in self.rows() {
+            if let Some(&score) = scores.get(&id)
+                && best.is_none_or(|(best, _)| score > best)
+            {
+                best = Some((score, id));
+            }
+        }
+        best.map(|(_, id)| id)
+    }
+
+    // the directories leading to the selection stay open, it would be lost otherwise
+    fn close_filter_opened(&mut self) {
+        let mut keep = HashSet::new();
+        let mut parent = self.selected.and_then(|id| self.nodes.get(&id)?.parent);
+        while let Some(id) = parent {
+            keep.insert(id);
+            parent = self.nodes[&id].parent;
+        }
+        for id in mem::take(&mut self.filter_opened) {
+            if !keep.contains(&id)
+                && let Some(node) = self.nodes.get_mut(&id)
+                && let NodeKind::Directory { open, .. } = &mut node.kind
+            {
+                *open = false;
+            }
+        }
+    }
+
+    // lines of the tree top to bottom: shown nodes with the menu, a new entry being
+    // named and placeholders for directories being read after their node
+    fn lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for id in self.rows() {
+            let node = &self.nodes[&id];
+            lines.push(Line::Node(id));
+            if self.menu && self.selected == Some(id) {
+                lines.extend(
+                    actions(node)
+                        .into_iter()
+                        .map(|(label, action)| Line::Action(id, label, action)),
+                );
+            }
+            if let Some(edit) = &self.edit
+                && (edit.target == EditTarget::NewFile(id)
+                    || edit.target == EditTarget::NewFolder(id))
+            {
+                lines.push(Line::Edit(node.depth + 1));
+            }
+            if let NodeKind::Directory {
+                open: true,
+                children: Children::Loading,
+            } = node.kind
+            {
+                lines.push(Line::Loading(node.depth + 1));
+            }
+        }
+        lines
+    }
+
+    // only the lines inside a viewport of the given height are built, spaces of the
+    // same height stand in for the rest
+    pub fn view(&self, height: f32) -> Column<'_, Message, theme::MyTheme> {
+        let lines = self.lines();
+        let visible = (height / ROW_HEIGHT).ceil() as usize + 1;
+        // the offset is stale while the content fits or right after it shrank
+        let first = ((self.offset / ROW_HEIGHT) as usize).min(lines.len().saturating_sub(visible));
+        let last = (first + visible).min(lines.len());
+
+        let mut column = Column::new().push(Space::with_height(first as f32 * ROW_HEIGHT));
+        for line in &lines[first..last] {
+            let line = match *line {
+                Line::Node(id) => {
+                    let node = &self.nodes[&id];
+                    match &self.edit {
+                        Some(edit) if edit.target == EditTarget::Rename(id) => {
+                            edit.view(node.depth)
                         }
-                    };
-                    elem.padding(Padding {
-                        top: 5.0,
-                        right: 5.0,
-                        bottom: 5.0,
-                        left: (node.indent as f32 + 1.0) * 15.0,
+                        _ => self.node_view(node),
+                    }
+                }
+                Line::Action(id, label, action) => {
+                    container(button(text(label)).on_press(Message::ProjectTreeAction(id, action)))
+                        .padding(Padding {
+                            left: (self.nodes[&id].depth as f32 + 2.0) * 15.0,
+                            ..Padding::ZERO
+                        })
+                        .into()
+                }
+                Line::Edit(depth) => match &self.edit {
+                    Some(edit) => edit.view(depth),
+                    None => Space::new(0.0, 0.0).into(),
+                },
+                Line::Loading(depth) => container(text("Loading...").style(theme::text::dimmed))
+                    .padding(Padding {
+                        left: (depth as f32 + 1.0) * 15.0,
+                        ..Padding::ZERO
                     })
-                    .into()
-                })
-                .collect();
-        Column::from_vec(nodes)
+                    .into(),
+            };
+            column = column.push(container(line).height(ROW_HEIGHT).center_y(ROW_HEIGHT));
+        }
+        column.push(Space::with_height((lines.len() - last) as f32 * ROW_HEIGHT))
+    }
+
+    fn node_view<'a>(&self, node: &'a Node) -> Element<'a, Message, theme::MyTheme> {
+        let highlighted = self.selected == Some(node.id) || self.hovered == Some(node.id);
+        // the highlight has its own text color
+        let style = match self.git.state(&node.path) {
+            _ if highlighted => theme::text::none,
+            Some(git::State::Modified) => theme::text::modified,
+            Some(git::State::Added) => theme::text::added,
+            Some(git::State::Untracked) => theme::text::untracked,
+            Some(git::State::Conflicted) => theme::text::conflicted,
+            Some(git::State::Ignored) => theme::text::dimmed,
+            None if node.dimmed => theme::text::dimmed,
+            None => theme::text::none,
+        };
+        let name = text(&node.name).style(style);
+        let content = match &node.kind {
+            NodeKind::File => Row::new()
+                .push(
+                    text(font::file_icon(&node.name))
+                        .font(font::ICON_SOLID)
+                        .style(style)
+                        .width(15.0),
+                )
+                .push(name)
+                .spacing(4.0),
+            NodeKind::Directory { open, .. } => {
+                let icon = if *open {
+                    font::caret_down()
+                } else {
+                    font::caret_right()
+                };
+                // something inside changed
+                let marker = self.git.is_changed(&node.path).then(|| {
+                    text("●").style(if highlighted {
+                        theme::text::none
+                    } else {
+                        theme::text::modified
+                    })
+                });
+                Row::new()
+                    .push(text(icon).font(font::ICON_SOLID).width(15.0))
+                    .push(name)
+                    .push_maybe(marker)
+                    .spacing(4.0)
+            }
+        };
+
+        let active = self.active.as_ref() == Some(&node.path);
+        let content = container(content)
+            .center_y(ROW_HEIGHT)
+            .padding(Padding {
+                right: 5.0,
+                left: (node.depth as f32 + 1.0) * 15.0,
+                ..Padding::ZERO
+            })
+            .style(if highlighted {
+                theme::container::selected
+            } else if active {
+                theme::container::active
+            } else {
+                theme::container::primary
+            });
+
+        // a press and release on the same node is a click, on another node a drag
+        mouse_area(content)
+            .on_press(Message::ProjectTreePress(node.id))
+            .on_release(Message::ProjectTreeRelease(node.id))
+            .on_right_press(Message::ProjectTreeMenu(node.id))
+            .on_enter(Message::ProjectTreeHover(Some(node.id)))
+            .on_exit(Message::ProjectTreeHover(None))
+            .into()
+    }
+}
+
+// actions offered for a node, the project root can't be renamed, copied or deleted
+fn actions(node: &Node) -> Vec<(&'static str, NodeAction)> {
+    let mut actions = vec![
+        ("New File", NodeAction::NewFile),
+        ("New Folder", NodeAction::NewFolder),
+    ];
+    if node.parent.is_some() {
+        actions.extend([
+            ("Rename", NodeAction::Rename),
+            ("Duplicate", NodeAction::Duplicate),
+            ("Delete", NodeAction::Delete),
+        ]);
+    }
+    actions.extend([
+        ("Copy Path", NodeAction::CopyPath),
+        ("Reveal", NodeAction::Reveal),
+    ]);
+    actions
+}
+
+// a line of the tree, each ROW_HEIGHT tall
+#[derive(Clone, Copy, PartialEq)]
+enum Line {
+    Node(usize),
+    // an action of the node's menu
+    Action(usize, &'static str, NodeAction),
+    // a new entry being named, at a depth
+    Edit(usize),
+    Loading(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeAction {
+    NewFile,
+    NewFolder,
+    Rename,
+    Duplicate,
+    Delete,
+    CopyPath,
+    Reveal,
+}
+
+// node ids, new entries go into a directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditTarget {
+    NewFile(usize),
+    NewFolder(usize),
+    Rename(usize),
+}
+
+// a name being typed in the tree
+pub struct Edit {
+    pub target: EditTarget,
+    pub text: String,
+    pub id: text_input::Id,
+}
+
+impl Edit {
+    pub fn new(target: EditTarget, text: String) -> Self {
+        Self {
+            target,
+            text,
+            id: text_input::Id::unique(),
+        }
+    }
+
+    fn view(&self, depth: usize) -> Element<'_, Message, theme::MyTheme> {
+        let placeholder = match self.target {
+            EditTarget::NewFile(_) => "File name",
+            EditTarget::NewFolder(_) => "Folder name",
+            EditTarget::Rename(_) => "New name",
+        };
+        Row::new()
+            .push(
+                text_input(placeholder, &self.text)
+                    .id(self.id.clone())
+                    .on_input(Message::ProjectTreeEditInput)
+                    .on_submit(Message::ProjectTreeEditSubmit)
+                    .width(200.0),
+            )
+            .push(button(text("Cancel")).on_press(Message::ProjectTreeEditCancel))
+            .spacing(4.0)
+            .padding(Padding {
+                left: (depth as f32 + 1.0) * 15.0,
+                ..Padding::ZERO
+            })
+            .into()
     }
 }
 
-#[derive(Clone)]
 pub struct Node {
     pub id: usize,
-    name: String,
-    pub indent: usize,
+    pub name: String,
     pub path: PathBuf,
+    pub parent: Option<usize>,
+    pub depth: usize,
     pub kind: NodeKind,
+    // hidden or ignored, only listed when asked for
+    dimmed: bool,
 }
 
-// https://stackoverflow.com/questions/49186751/sharing-a-common-value-in-all-enum-values
-#[derive(Clone, Copy)]
 pub enum NodeKind {
     File,
-    Directory { open: bool },
+    // children are kept while closed
+    Directory { open: bool, children: Children },
 }
 
-impl Node {
-    fn new(path: PathBuf, id: usize, indent: usize) -> Self {
-        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+pub enum Children {
+    Unloaded,
+    Loading,
+    Loaded(Vec<usize>),
+}
 
-        return if path.is_dir() {
-            Self {
-                id: id,
-                name: name,
-                path: path,
-                indent: indent,
-                kind: NodeKind::Directory { open: false },
+impl Node {
+    fn new(entry: Entry, id: usize, parent: Option<usize>, depth: usize) -> Self {
+        let name = entry.path.file_name().map_or_else(
+            || entry.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let kind = if entry.is_dir {
+            NodeKind::Directory {
+                open: false,
+                children: Children::Unloaded,
             }
         } else {
-            Self {
-                id: id,
-                name: name,
-                path: path,
-                indent: indent,
-                kind: NodeKind::File,
-            }
+            NodeKind::File
         };
+
+        Self {
+            id,
+            name,
+            path: entry.path,
+            parent,
+            depth,
+            kind,
+            dimmed: entry.dimmed,
+        }
+    }
+}
+
+// a directory entry with what the tree shows of it, found off the ui thread
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub dimmed: bool,
+}
+
+// entries of a directory, directories first, then by name.
+// hidden and ignored entries are left out unless show_hidden
+pub fn list(dir: &Path, matcher: Option<&Matcher>, show_hidden: bool) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        // follows symlinks
+        let is_dir = path.is_dir();
+        let dimmed = matcher
+            .is_some_and(|matcher| matcher.is_hidden(&path) || matcher.is_ignored(&path, is_dir));
+        if dimmed && !show_hidden {
+            continue;
+        }
+        entries.push(Entry {
+            path,
+            is_dir,
+            dimmed,
+        });
     }
+    entries.sort_by(|a, b| match (a.is_dir, b.is_dir) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        _ => a.path.cmp(&b.path),
+    });
+    Ok(entries)
 }
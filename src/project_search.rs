@@ -0,0 +1,539 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use cosmic_text::Cursor;
+use iced::{
+    Alignment, Element, Length, Padding, Task,
+    widget::{self, Column, Row, button, checkbox, scrollable, text, text_input},
+};
+use ignore::overrides::OverrideBuilder;
+
+use crate::{Message, background, file, matcher, search, theme};
+
+// stop collecting after this many hits, the pane is not meant for more
+const MAX_HITS: usize = 10_000;
+const MAX_PREVIEW: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub hits: Vec<Hit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub found: search::Match,
+    // the whole matching line
+    pub line: String,
+    // whether replace all touches this hit
+    pub included: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Results {
+    pub files: Vec<FileResult>,
+    pub truncated: bool,
+    // what the results were searched with, replacements need the same pattern
+    pub query: String,
+    pub options: search::Options,
+}
+
+pub struct ProjectSearch {
+    query_id: text_input::Id,
+    query: String,
+    // comma separated globs
+    include: String,
+    exclude: String,
+    options: search::Options,
+    replace: String,
+    // bumped on every run, results of older runs are dropped
+    generation: usize,
+    searching: bool,
+    // root of the last run, results are shown relative to it
+    root: Option<PathBuf>,
+    results: Option<Results>,
+    error: Option<String>,
+    // replacements in open tabs whose lines changed since the search
+    skipped: usize,
+}
+
+impl ProjectSearch {
+    pub fn new() -> Self {
+        Self {
+            query_id: text_input::Id::unique(),
+            query: String::new(),
+            include: String::new(),
+            exclude: String::new(),
+            options: search::Options::default(),
+            replace: String::new(),
+            generation: 0,
+            searching: false,
+            root: None,
+            results: None,
+            error: None,
+            skipped: 0,
+        }
+    }
+
+    pub fn focus(&self) -> Task<Message> {
+        widget::text_input::focus(self.query_id.clone())
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+    }
+
+    pub fn set_include(&mut self, include: String) {
+        self.include = include;
+    }
+
+    pub fn set_exclude(&mut self, exclude: String) {
+        self.exclude = exclude;
+    }
+
+    pub fn set_replace(&mut self, replace: String) {
+        self.replace = replace;
+    }
+
+    pub fn toggle(&mut self, toggle: search::Toggle) {
+        self.options.toggle(toggle);
+    }
+
+    // includes or excludes a single hit from replace all
+    pub fn toggle_hit(&mut self, file: usize, hit: usize) {
+        if let Some(hit) = self
+            .results
+            .as_mut()
+            .and_then(|results| results.files.get_mut(file))
+            .and_then(|file| file.hits.get_mut(hit))
+        {
+            hit.included = !hit.included;
+        }
+    }
+
+    // includes every hit of the file, unless all are included already
+    pub fn toggle_file(&mut self, file: usize) {
+        if let Some(file) = self
+            .results
+            .as_mut()
+            .and_then(|results| results.files.get_mut(file))
+        {
+            let included = !file.hits.iter().all(|hit| hit.included);
+            for hit in file.hits.iter_mut() {
+                hit.included = included;
+            }
+        }
+    }
+
+    // included hits grouped by file
+    pub fn replacements(&self) -> Vec<(PathBuf, Vec<search::Replacement>)> {
+        let Some(results) = &self.results else {
+            return Vec::new();
+        };
+        let Ok(regex) = search::build(&results.query, results.options) else {
+            return Vec::new();
+        };
+
+        results
+            .files
+            .iter()
+            .map(|file| {
+                let replacements = file
+                    .hits
+                    .iter()
+                    .filter(|hit| hit.included)
+                    .map(|hit| search::Replacement {
+                        found: hit.found,
+                        line: hit.line.clone(),
+                        text: search::replacement(
+                            &regex,
+                            &hit.line,
+                            &hit.found,
+                            &self.replace,
+                            results.options,
+                        ),
+                    })
+                    .collect();
+                (file.path.clone(), replacements)
+            })
+            .filter(|(_, replacements): &(PathBuf, Vec<search::Replacement>)| {
+                !replacements.is_empty()
+            })
+            .collect()
+    }
+
+    // rewrites files that are not open in a tab, finishes with Message::ProjectSearchReplaceDone.
+    // skipped counts the replacements open tabs could not make
+    pub fn replace_on_disk(
+        &mut self,
+        files: Vec<(PathBuf, Vec<search::Replacement>)>,
+        skipped: usize,
+    ) -> Task<Message> {
+        self.skipped = skipped;
+        if files.is_empty() {
+            return Task::done(Message::ProjectSearchReplaceDone(Ok(())));
+        }
+
+        self.searching = true;
+        Task::perform(
+            background(move || {
+                for (path, replacements) in files.iter() {
+                    replace_file(path, replacements).map_err(|err| {
+                        format!("could not replace in {}: {}", path.display(), err)
+                    })?;
+                }
+                Ok(())
+            }),
+            Message::ProjectSearchReplaceDone,
+        )
+    }
+
+    pub fn replace_done(&mut self, result: Result<(), String>) {
+        if let Err(err) = result {
+            log::error!("{}", err);
+            self.error = Some(err);
+        } else if self.skipped > 0 {
+            self.error = Some(format!(
+                "Skipped {} replacements, their lines changed since the search",
+                self.skipped
+            ));
+        }
+        self.skipped = 0;
+    }
+
+    // searches on a background thread, finishes with Message::ProjectSearchDone.
+    // open files are searched in their tab's text, which may differ from the disk
+    pub fn run(
+        &mut self,
+        walk: Option<matcher::Walk>,
+        open: HashMap<PathBuf, String>,
+    ) -> Task<Message> {
+        self.generation += 1;
+        self.error = None;
+        // a pending search is stale once the generation changes
+        self.searching = false;
+        self.results = None;
+
+        let Some(walk) = walk else {
+            self.error = Some("Open a directory to search".to_string());
+            return Task::none();
+        };
+        if self.query.is_empty() {
+            return Task::none();
+        }
+
+        self.searching = true;
+        self.root = Some(walk.root().to_path_buf());
+        let generation = self.generation;
+        let query = self.query.clone();
+        let options = self.options;
+        let include = globs(&self.include);
+        let exclude = globs(&self.exclude);
+        Task::perform(
+            background(move || search_files(&walk, &open, &query, options, &include, &exclude)),
+            move |results| Message::ProjectSearchDone(generation, results),
+        )
+    }
+
+    pub fn done(&mut self, generation: usize, results: Result<Results, String>) {
+        if generation != self.generation {
+            return;
+        }
+        self.searching = false;
+        match results {
+            Ok(results) => self.results = Some(results),
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    pub fn view(&self) -> Column<'_, Message, theme::MyTheme> {
+        let inputs = Column::new()
+            .push(
+                Row::new()
+                    .push(
+                        text_input("Search Project...", &self.query)
+                            .on_input(Message::ProjectSearchQuery)
+                            .on_submit(Message::ProjectSearchRun)
+                            .id(self.query_id.clone()),
+                    )
+                    .push(search::toggle_button(
+                        "Aa",
+                        self.options.case_sensitive,
+                        Message::ProjectSearchToggle(search::Toggle::CaseSensitive),
+                    ))
+                    .push(search::toggle_button(
+                        "ab",
+                        self.options.whole_word,
+                        Message::ProjectSearchToggle(search::Toggle::WholeWord),
+                    ))
+                    .push(search::toggle_button(
+                        ".*",
+                        self.options.regex,
+                        Message::ProjectSearchToggle(search::Toggle::Regex),
+                    ))
+                    .align_y(Alignment::Center)
+                    .spacing(4.0),
+            )
+            .push(
+                text_input("Include: *.rs, src/**", &self.include)
+                    .on_input(Message::ProjectSearchInclude)
+                    .on_submit(Message::ProjectSearchRun),
+            )
+            .push(
+                text_input("Exclude: target/**", &self.exclude)
+                    .on_input(Message::ProjectSearchExclude)
+                    .on_submit(Message::ProjectSearchRun),
+            )
+            .push(
+                Row::new()
+                    .push(
+                        text_input("Replace...", &self.replace)
+                            .on_input(Message::ProjectSearchReplace),
+                    )
+                    .push(button(text("All")).on_press(Message::ProjectSearchReplaceAll))
+                    .align_y(Alignment::Center)
+                    .spacing(4.0),
+            )
+            .spacing(4.0);
+
+        // preview is only shown once there is something to replace with
+        let regex = self
+            .results
+            .as_ref()
+            .filter(|_| !self.replace.is_empty())
+            .and_then(|results| search::build(&results.query, results.options).ok());
+
+        let status = if let Some(error) = &self.error {
+            error.clone()
+        } else if self.searching {
+            "Searching...".to_string()
+        } else if let Some(results) = &self.results {
+            let hits: usize = results.files.iter().map(|file| file.hits.len()).sum();
+            format!(
+                "{}{} results in {} files",
+                hits,
+                if results.truncated { "+" } else { "" },
+                results.files.len()
+            )
+        } else {
+            String::new()
+        };
+
+        let mut rows: Vec<Element<Message, theme::MyTheme>> = Vec::new();
+        if let Some(results) = &self.results {
+            for (file_i, file) in results.files.iter().enumerate() {
+                let name = self
+                    .root
+                    .as_ref()
+                    .and_then(|root| file.path.strip_prefix(root).ok())
+                    .unwrap_or(&file.path);
+                rows.push(
+                    checkbox(
+                        format!("{} ({})", name.display(), file.hits.len()),
+                        file.hits.iter().all(|hit| hit.included),
+                    )
+                    .on_toggle(move |_| Message::ProjectSearchToggleFile(file_i))
+                    .into(),
+                );
+                for (hit_i, hit) in file.hits.iter().enumerate() {
+                    let cursor = Cursor::new(hit.found.line, hit.found.start);
+                    let line_number = hit.found.line + 1;
+                    let content: Element<Message, theme::MyTheme> = match &regex {
+                        Some(regex) => {
+                            let replacement = search::replacement(
+                                regex,
+                                &hit.line,
+                                &hit.found,
+                                &self.replace,
+                                results.options,
+                            );
+                            let replaced = format!(
+                                "{}{}{}",
+                                &hit.line[..hit.found.start],
+                                replacement,
+                                &hit.line[hit.found.end..]
+                            );
+                            Column::new()
+                                .push(
+                                    text(format!("{}: - {}", line_number, preview(&hit.line)))
+                                        .style(theme::text::removed),
+                                )
+                                .push(
+                                    text(format!("{}: + {}", line_number, preview(&replaced)))
+                                        .style(theme::text::added),
+                                )
+                                .into()
+                        }
+                        None => text(format!("{}: {}", line_number, preview(&hit.line))).into(),
+                    };
+                    rows.push(
+                        Row::new()
+                            .push(
+                                checkbox("", hit.included).on_toggle(move |_| {
+                                    Message::ProjectSearchToggleHit(file_i, hit_i)
+                                }),
+                            )
+                            .push(
+                                button(content)
+                                    .on_press(Message::OpenFile(file.path.clone(), Some(cursor)))
+                                    .padding(Padding {
+                                        top: 2.0,
+                                        right: 5.0,
+                                        bottom: 2.0,
+                                        left: 5.0,
+                                    })
+                                    .width(Length::Fill),
+                            )
+                            .align_y(Alignment::Center)
+                            .padding(Padding {
+                                left: 15.0,
+                                ..Padding::ZERO
+                            })
+                            .into(),
+                    );
+                }
+            }
+        }
+
+        Column::new()
+            .push(inputs)
+            .push(text(status))
+            .push(scrollable(Column::from_vec(rows)).height(Length::Fill))
+            .spacing(4.0)
+            .padding(5.0)
+    }
+}
+
+fn globs(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|glob| glob.trim())
+        .filter(|glob| !glob.is_empty())
+        .map(|glob| glob.to_string())
+        .collect()
+}
+
+// walks the project without ignored files, unless an include glob names them
+fn search_files(
+    walk: &matcher::Walk,
+    open: &HashMap<PathBuf, String>,
+    query: &str,
+    options: search::Options,
+    include: &[String],
+    exclude: &[String],
+) -> Result<Results, String> {
+    let regex = search::build(query, options).map_err(|_| "Invalid pattern".to_string())?;
+
+    let mut overrides = OverrideBuilder::new(walk.root());
+    for glob in include {
+        overrides.add(glob).map_err(|err| err.to_string())?;
+    }
+    for glob in exclude {
+        overrides
+            .add(&format!("!{}", glob))
+            .map_err(|err| err.to_string())?;
+    }
+    let overrides = overrides.build().map_err(|err| err.to_string())?;
+
+    let mut files = Vec::new();
+    let mut total = 0;
+    let mut truncated = false;
+    for entry in walk.build_including(include).overrides(overrides).build() {
+        let entry = match entry {
+            Ok(ok) => ok,
+            Err(err) => {
+                log::warn!("could not walk project: {}", err);
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        // skips binary and non utf-8 files
+        let content = match open.get(entry.path()) {
+            Some(text) => text.clone(),
+            None => match fs::read_to_string(entry.path()) {
+                Ok(content) => content,
+                Err(_) => continue,
+            },
+        };
+
+        let mut hits = Vec::new();
+        let mut matches = Vec::new();
+        for (line_i, line) in content.lines().enumerate() {
+            matches.clear();
+            search::find_in_line(&regex, line_i, line, &mut matches);
+            for found in matches.iter() {
+                hits.push(Hit {
+                    found: *found,
+                    line: line.to_string(),
+                    included: true,
+                });
+            }
+        }
+        if hits.is_empty() {
+            continue;
+        }
+
+        total += hits.len();
+        files.push(FileResult {
+            path: entry.path().to_path_buf(),
+            hits,
+        });
+        if total >= MAX_HITS {
+            truncated = true;
+            break;
+        }
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Results {
+        files,
+        truncated,
+        query: query.to_string(),
+        options,
+    })
+}
+
+fn preview(line: &str) -> String {
+    let line = line.trim_start();
+    match line.char_indices().nth(MAX_PREVIEW) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+// applies replacements to a file on disk, keeping its line endings
+fn replace_file(path: &Path, replacements: &[search::Replacement]) -> io::Result<()> {
+    let content = fs::read_to_string(path)?;
+    let mut replaced = String::with_capacity(content.len());
+    let mut replacements = replacements.iter().peekable();
+    // line numbers count like str::lines, which the search used
+    for (line_i, segment) in content.split_inclusive('\n').enumerate() {
+        let line = segment
+            .strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(segment);
+        let ending = &segment[line.len()..];
+
+        let mut last = 0;
+        while let Some(replacement) = replacements.next_if(|r| r.found.line == line_i) {
+            if line != replacement.line {
+                return Err(io::Error::other("file changed since it was searched"));
+            }
+            replaced.push_str(&line[last..replacement.found.start]);
+            replaced.push_str(&replacement.text);
+            last = replacement.found.end;
+        }
+        replaced.push_str(&line[last..]);
+        replaced.push_str(ending);
+    }
+    // lines past the end, the file got shorter
+    if replacements.peek().is_some() {
+        return Err(io::Error::other("file changed since it was searched"));
+    }
+
+    file::write_atomic(path, replaced.as_bytes())
+}
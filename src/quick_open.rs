@@ -0,0 +1,380 @@
+use std::path::{Path, PathBuf};
+
+use iced::{
+    Element, Event, Length, Padding, Subscription, Task, event,
+    keyboard::{self, Key, key::Named},
+    widget::{
+        self, Column, button, column, container, mouse_area, opaque, row,
+        scrollable::{self, RelativeOffset},
+        text, text_input,
+    },
+};
+
+use crate::{Message, background, fuzzy, matcher, theme};
+
+// a walk stops here, the finder is not meant for more
+const MAX_FILES: usize = 200_000;
+const MAX_RESULTS: usize = 50;
+const MAX_RECENT: usize = 32;
+// a match inside the file name beats one spread over the path
+const NAME_BONUS: i64 = 16;
+
+// a file under the project root
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    // relative to the root, with / between segments
+    relative: String,
+    // where the file name starts in relative
+    name: usize,
+}
+
+// finds a file of the project by a few letters of its path
+pub struct QuickOpen {
+    pub open: bool,
+    input_id: text_input::Id,
+    query: String,
+    files: Vec<Candidate>,
+    // bumped on every walk, files of older walks are dropped
+    generation: usize,
+    indexing: bool,
+    // most recently opened first
+    recent: Vec<PathBuf>,
+    // indices into files
+    picker: Picker,
+}
+
+impl QuickOpen {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            input_id: text_input::Id::unique(),
+            query: String::new(),
+            files: Vec::new(),
+            generation: 0,
+            indexing: false,
+            recent: Vec::new(),
+            picker: Picker::new(),
+        }
+    }
+
+    // files are walked again on every show, the last ones are listed meanwhile
+    pub fn show(&mut self, walk: Option<matcher::Walk>) -> Task<Message> {
+        self.open = true;
+        self.query.clear();
+        self.generation += 1;
+        self.rank();
+
+        let focus = widget::text_input::focus(self.input_id.clone());
+        let Some(walk) = walk else {
+            self.files.clear();
+            self.picker.set_results(Vec::new());
+            return focus;
+        };
+        self.indexing = true;
+        let generation = self.generation;
+        let index = Task::perform(background(move || index(&walk)), move |files| {
+            Message::QuickOpenIndexed(generation, files)
+        });
+        Task::batch([focus, index])
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.indexing = false;
+    }
+
+    pub fn indexed(&mut self, generation: usize, files: Vec<Candidate>) {
+        if generation != self.generation {
+            return;
+        }
+        self.indexing = false;
+        self.files = files;
+        self.rank();
+    }
+
+    pub fn set_query(&mut self, query: String) -> Task<Message> {
+        self.query = query;
+        self.rank();
+        self.picker.scroll_to_selected()
+    }
+
+    // a file was opened, it ranks higher for a while
+    pub fn opened(&mut self, path: &Path) {
+        self.recent.retain(|recent| recent != path);
+        self.recent.insert(0, path.to_path_buf());
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    pub fn key_press(&mut self, key: &Key) -> Option<Task<Message>> {
+        self.picker.key_press(key)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.picker.select(index);
+    }
+
+    // the selected file and the position asked for with path:line:col
+    pub fn choice(&self) -> Option<Message> {
+        let file = &self.files[self.picker.choice()?];
+        let (_, line, column) = split_position(&self.query);
+        let path = file.path.clone();
+        Some(match line {
+            Some(line) => Message::OpenFileAt(
+                path,
+                line.saturating_sub(1),
+                column.map_or(0, |column| column.saturating_sub(1)),
+            ),
+            None => Message::OpenFile(path, None),
+        })
+    }
+
+    // best matches first. without a query recent files lead, then the shortest paths
+    fn rank(&mut self) {
+        let (query, _, _) = split_position(&self.query);
+
+        let mut ranked: Vec<(i64, usize)> = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(index, file)| {
+                let score = if query.is_empty() {
+                    0
+                } else {
+                    score(query, file)?
+                };
+                Some((score + self.recent_bonus(&file.path), index))
+            })
+            .collect();
+        // ties go to the shorter path
+        ranked.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then_with(|| {
+                self.files[*a]
+                    .relative
+                    .len()
+                    .cmp(&self.files[*b].relative.len())
+            })
+        });
+        ranked.truncate(MAX_RESULTS);
+        self.picker
+            .set_results(ranked.into_iter().map(|(_, index)| index).collect());
+    }
+
+    fn recent_bonus(&self, path: &Path) -> i64 {
+        self.recent
+            .iter()
+            .position(|recent| recent == path)
+            .map_or(0, |position| (MAX_RECENT - position) as i64)
+    }
+
+    pub fn view(&self) -> Element<'_, Message, theme::MyTheme> {
+        let input = text_input("Go to file, path:line:col", &self.query)
+            .id(self.input_id.clone())
+            .on_input(Message::QuickOpenQuery)
+            .on_submit(Message::QuickOpenSubmit);
+
+        let list = self.picker.view(
+            |index| {
+                let file = &self.files[index];
+                let (dir, name) = file.relative.split_at(file.name);
+                row![text(name), text(dir).style(theme::text::dimmed)]
+                    .spacing(8.0)
+                    .into()
+            },
+            Message::QuickOpenSelect,
+        );
+
+        let status = if self.indexing && self.files.is_empty() {
+            Some("Indexing...")
+        } else if self.files.is_empty() {
+            Some("Open a directory to find its files")
+        } else if self.picker.is_empty() {
+            Some("No matching files")
+        } else {
+            None
+        };
+
+        let content = column![input, list]
+            .push_maybe(status.map(|status| text(status).style(theme::text::dimmed)))
+            .spacing(8.0);
+        overlay(content)
+    }
+}
+
+// the results of a picker, like quick open or the palette, with one selected.
+// up and down move the selection around, the list scrolls along
+pub struct Picker {
+    scroll_id: scrollable::Id,
+    // indices into what is picked from, best first
+    results: Vec<usize>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new() -> Self {
+        Self {
+            scroll_id: scrollable::Id::unique(),
+            results: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    // new results select the best one
+    pub fn set_results(&mut self, results: Vec<usize>) {
+        self.results = results;
+        self.selected = 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn choice(&self) -> Option<usize> {
+        self.results.get(self.selected).copied()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.results.len().saturating_sub(1));
+    }
+
+    // up and down move the selection, the input lets them through
+    pub fn key_press(&mut self, key: &Key) -> Option<Task<Message>> {
+        if self.results.is_empty() {
+            return None;
+        }
+        let last = self.results.len() - 1;
+        self.selected = match key {
+            Key::Named(Named::ArrowUp) => self.selected.checked_sub(1).unwrap_or(last),
+            Key::Named(Named::ArrowDown) if self.selected == last => 0,
+            Key::Named(Named::ArrowDown) => self.selected + 1,
+            _ => return None,
+        };
+        Some(self.scroll_to_selected())
+    }
+
+    pub fn scroll_to_selected(&self) -> Task<Message> {
+        let y = self.selected as f32 / self.results.len().saturating_sub(1).max(1) as f32;
+        scrollable::snap_to(self.scroll_id.clone(), RelativeOffset { x: 0.0, y })
+    }
+
+    // a button per result, item draws the result at an index, on_select gets its position
+    pub fn view<'a>(
+        &self,
+        item: impl Fn(usize) -> Element<'a, Message, theme::MyTheme>,
+        on_select: fn(usize) -> Message,
+    ) -> Element<'a, Message, theme::MyTheme> {
+        let mut list = Column::new();
+        for (position, index) in self.results.iter().enumerate() {
+            let style = if position == self.selected {
+                theme::button::toggled
+            } else {
+                theme::button::primary
+            };
+            list = list.push(
+                button(item(*index))
+                    .on_press(on_select(position))
+                    .style(style)
+                    .width(Length::Fill),
+            );
+        }
+        widget::scrollable(list)
+            .id(self.scroll_id.clone())
+            .height(Length::Shrink)
+            .into()
+    }
+}
+
+// escape closes an open picker. the input takes escape before the key bindings see it
+pub fn escape() -> Subscription<Message> {
+    event::listen_with(|event, _, _| match event {
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key: Key::Named(Named::Escape),
+            ..
+        }) => Some(Message::PickerClose),
+        _ => None,
+    })
+}
+
+// a dialog over the top of the window, a click beside it closes the picker
+pub fn overlay<'a>(
+    content: impl Into<Element<'a, Message, theme::MyTheme>>,
+) -> Element<'a, Message, theme::MyTheme> {
+    let dialog = container(content)
+        .padding(10)
+        .width(600.0)
+        .max_height(500.0)
+        .style(theme::container::dialog);
+
+    let overlay = container(opaque(dialog))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x(Length::Fill)
+        .padding(Padding {
+            top: 60.0,
+            ..Padding::ZERO
+        });
+    opaque(mouse_area(overlay).on_press(Message::PickerClose))
+}
+
+// ranks a file by its relative path, or by its name alone if that is better
+fn score(query: &str, file: &Candidate) -> Option<i64> {
+    let path = fuzzy::score(query, &file.relative)?;
+    let name = fuzzy::score(query, &file.relative[file.name..]).map(|score| score + NAME_BONUS);
+    Some(name.map_or(path, |name| name.max(path)))
+}
+
+// "src/main.rs:10:4" is the path with a line and a column, counted from 1
+fn split_position(query: &str) -> (&str, Option<usize>, Option<usize>) {
+    let mut parts = query.rsplitn(3, ':');
+    let last = parts.next().unwrap_or_default();
+    let middle = parts.next();
+    let first = parts.next();
+    match (
+        first,
+        middle.map(str::parse::<usize>),
+        last.parse::<usize>(),
+    ) {
+        (Some(path), Some(Ok(line)), Ok(column)) => (path, Some(line), Some(column)),
+        (_, _, Ok(line)) => match query.rsplit_once(':') {
+            Some((path, _)) => (path, Some(line), None),
+            None => (query, None, None),
+        },
+        _ => (query, None, None),
+    }
+}
+
+// files of the project without ignored ones, dotfiles only when the tree shows them
+fn index(walk: &matcher::Walk) -> Vec<Candidate> {
+    let root = walk.root();
+    let mut files = Vec::new();
+    for entry in walk.build().build() {
+        let entry = match entry {
+            Ok(ok) => ok,
+            Err(err) => {
+                log::warn!("could not walk project: {}", err);
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let relative: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let relative = relative.join("/");
+        files.push(Candidate {
+            name: relative.rfind('/').map_or(0, |slash| slash + 1),
+            relative,
+            path: entry.into_path(),
+        });
+        if files.len() >= MAX_FILES {
+            log::warn!("quick open stops at {} files", MAX_FILES);
+            break;
+        }
+    }
+    files
+}
@@ -0,0 +1,227 @@
+use iced::widget::{Button, button, text};
+use regex::{Regex, RegexBuilder};
+
+use crate::{Message, theme};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Toggle {
+    CaseSensitive,
+    WholeWord,
+    Regex,
+    PreserveCase,
+    InSelection,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Options {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    // replacing foo with bar turns Foo into Bar
+    pub preserve_case: bool,
+    pub in_selection: bool,
+}
+
+impl Options {
+    pub fn toggle(&mut self, toggle: Toggle) {
+        match toggle {
+            Toggle::CaseSensitive => self.case_sensitive = !self.case_sensitive,
+            Toggle::WholeWord => self.whole_word = !self.whole_word,
+            Toggle::Regex => self.regex = !self.regex,
+            Toggle::PreserveCase => self.preserve_case = !self.preserve_case,
+            Toggle::InSelection => self.in_selection = !self.in_selection,
+        }
+    }
+}
+
+// a match inside a single buffer line, start and end are byte indices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// a replacement computed from a copy of the text, like a file on disk
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub found: Match,
+    // line the match was found in
+    pub line: String,
+    pub text: String,
+}
+
+// plain text is escaped, so every query goes through the regex engine
+pub fn build(query: &str, options: Options) -> Result<Regex, regex::Error> {
+    let mut pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+}
+
+// matches never span lines, empty matches are skipped
+pub fn find_in_line(regex: &Regex, line: usize, text: &str, matches: &mut Vec<Match>) {
+    for found in regex.find_iter(text) {
+        if found.start() == found.end() {
+            continue;
+        }
+        matches.push(Match {
+            line,
+            start: found.start(),
+            end: found.end(),
+        });
+    }
+}
+
+// text that replaces a match, regex mode expands $1 and ${name} captures
+pub fn replacement(
+    regex: &Regex,
+    text: &str,
+    found: &Match,
+    replace: &str,
+    options: Options,
+) -> String {
+    let mut replaced = String::new();
+    match regex.captures_at(text, found.start) {
+        Some(captures) if options.regex => captures.expand(replace, &mut replaced),
+        _ => replaced.push_str(replace),
+    }
+
+    if options.preserve_case {
+        preserve_case(&text[found.start..found.end], &replaced)
+    } else {
+        replaced
+    }
+}
+
+// FOO -> BAR, Foo -> Bar, foo -> bar
+fn preserve_case(original: &str, replacement: &str) -> String {
+    let letters: Vec<char> = original.chars().filter(|c| c.is_alphabetic()).collect();
+    let Some(first) = letters.first() else {
+        return replacement.to_string();
+    };
+
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if letters.iter().all(|c| c.is_lowercase()) {
+        replacement.to_lowercase()
+    } else if first.is_uppercase() {
+        let mut chars = replacement.chars();
+        match chars.next() {
+            Some(c) => c.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        replacement.to_string()
+    }
+}
+
+// option toggle shared by the tab search bar, the project search pane and the project tree
+pub fn toggle_button<'a>(
+    label: &'a str,
+    active: bool,
+    on_press: Message,
+) -> Button<'a, Message, theme::MyTheme> {
+    button(text(label))
+        .style(move |style, status| {
+            if active {
+                theme::button::toggled(style, status)
+            } else {
+                theme::button::primary(style, status)
+            }
+        })
+        .on_press(on_press)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // replaces every match in text, like replace all does in a line
+    fn replace_all(text: &str, query: &str, replace: &str, options: Options) -> String {
+        let regex = build(query, options).unwrap();
+        let mut matches = Vec::new();
+        find_in_line(&regex, 0, text, &mut matches);
+        let mut replaced = String::new();
+        let mut last = 0;
+        for found in &matches {
+            replaced.push_str(&text[last..found.start]);
+            replaced.push_str(&replacement(&regex, text, found, replace, options));
+            last = found.end;
+        }
+        replaced.push_str(&text[last..]);
+        replaced
+    }
+
+    #[test]
+    fn preserves_case() {
+        let options = Options {
+            preserve_case: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            replace_all("foo Foo FOO", "foo", "bar", options),
+            "bar Bar BAR"
+        );
+        // a mixed case source keeps the replacement as typed
+        assert_eq!(replace_all("fOo", "foo", "bAr", options), "bAr");
+    }
+
+    #[test]
+    fn ignores_case_unless_asked() {
+        let options = Options::default();
+        assert_eq!(replace_all("foo Foo", "foo", "bar", options), "bar bar");
+        let options = Options {
+            case_sensitive: true,
+            ..Options::default()
+        };
+        assert_eq!(replace_all("foo Foo", "foo", "bar", options), "bar Foo");
+    }
+
+    #[test]
+    fn expands_captures() {
+        let options = Options {
+            regex: true,
+            case_sensitive: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            replace_all("let a = b;", r"(\w+) = (\w+)", "$2 = $1", options),
+            "let b = a;"
+        );
+        assert_eq!(
+            replace_all("fn main()", r"fn (?<name>\w+)", "fn ${name}_old", options),
+            "fn main_old()"
+        );
+        // plain text mode takes $ literally
+        let options = Options::default();
+        assert_eq!(replace_all("a", "a", "$1", options), "$1");
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        let options = Options {
+            whole_word: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            replace_all("foo foobar barfoo foo", "foo", "x", options),
+            "x foobar barfoo x"
+        );
+        // the pattern is grouped, so both alternatives are bounded
+        let options = Options {
+            whole_word: true,
+            regex: true,
+            ..Options::default()
+        };
+        assert_eq!(replace_all("ab a b", "a|b", "x", options), "ab x x");
+    }
+}
@@ -1,14 +1,22 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 use std::{fs, io};
 
-use cosmic_text::{Attrs, Buffer, Edit, Metrics, SyntaxEditor, SyntaxSystem};
+use cosmic_text::{
+    Attrs, Buffer, Cursor, Edit, LineEnding, LineIter, Metrics, Selection, SyntaxEditor,
+    SyntaxSystem,
+};
 use iced::advanced::widget::operate;
-use iced::widget::{self, Column, Scrollable, scrollable, text_input};
-use iced::{Element, Length, Task, advanced};
+use iced::widget::{self, Column, Row, Scrollable, button, scrollable, text, text_input};
+use iced::{Alignment, Element, Length, Task, advanced};
 use iced_aw::TabBar;
+use similar::{ChangeTag, TextDiff};
 
-use crate::{FONT_SYSTEM, Message, SYNTAX_SYSTEM, text_box, theme};
+use crate::history::{self, History};
+use crate::{
+    FONT_SYSTEM, Message, SYNTAX_SYSTEM, file, key_binds::Profile, keymap, search, text_box, theme,
+};
 
 // TODO: use iced editor as an example for content RwLock
 // TODO: use viewer(model) instead of model.view()
@@ -16,20 +24,31 @@ use crate::{FONT_SYSTEM, Message, SYNTAX_SYSTEM, text_box, theme};
 pub struct TabView {
     active: Option<usize>,
     tabs: Vec<Tab>,
+    // undo steps kept per tab
+    undo_limit: usize,
+    backup: file::Backup,
 }
 
 impl TabView {
-    pub fn new() -> Self {
+    pub fn new(undo_limit: usize, backup: file::Backup) -> Self {
         Self {
             active: None,
             tabs: Vec::new(),
+            undo_limit,
+            backup,
         }
     }
 
     pub fn insert(&mut self, path: Option<PathBuf>) -> io::Result<usize> {
-        let mut tab = Tab::new();
+        let mut tab = Tab::new(self.undo_limit, self.backup);
         if let Some(path) = path {
             tab.open_file(path)?;
+        } else {
+            // lowest number no other untitled tab uses
+            let number = (1..)
+                .find(|number| self.tabs.iter().all(|tab| tab.untitled != Some(*number)))
+                .unwrap();
+            tab.untitled = Some(number);
         }
         self.tabs.push(tab);
         Ok(self.tabs.len() - 1)
@@ -43,7 +62,7 @@ impl TabView {
             return;
         };
 
-        self.tabs.remove(index);
+        self.tabs.remove(index).store_history();
 
         // check shift left
         let last_active = if let Some(active) = self.active {
@@ -78,10 +97,82 @@ impl TabView {
         self.active
     }
 
+    // activates the tab delta places away, wrapping around the ends
+    pub fn cycle(&mut self, delta: isize) {
+        if let Some(active) = self.active {
+            let len = self.tabs.len() as isize;
+            self.active = Some((active as isize + delta).rem_euclid(len) as usize);
+        }
+    }
+
+    // gives the keys to the editor of the active tab
+    pub fn focus(&self) -> Task<Message> {
+        match self.active {
+            Some(active) => self.tabs[active].focus(),
+            None => Task::none(),
+        }
+    }
+
+    pub fn is_dirty(&self, index: usize) -> bool {
+        self.tabs.get(index).is_some_and(|tab| tab.is_dirty())
+    }
+
+    // index and name of every tab with unsaved changes
+    pub fn dirty(&self) -> Vec<(usize, String)> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .filter(|(_, tab)| tab.is_dirty())
+            .map(|(index, tab)| (index, tab.get_name()))
+            .collect()
+    }
+
+    // before the editor exits
+    pub fn store_histories(&self) {
+        for tab in self.tabs.iter() {
+            tab.store_history();
+        }
+    }
+
     pub fn tab_mut(&mut self, index: usize) -> Option<&mut Tab> {
         self.tabs.get_mut(index)
     }
 
+    // a file or directory was renamed or moved, tabs of files in it save to the new place
+    pub fn rename_path(&mut self, from: &Path, to: &Path) {
+        for tab in self.tabs.iter_mut() {
+            if let Some(path) = &tab.file_path
+                && let Ok(rest) = path.strip_prefix(from)
+            {
+                tab.file_path = Some(if rest.as_os_str().is_empty() {
+                    to.to_path_buf()
+                } else {
+                    to.join(rest)
+                });
+            }
+        }
+    }
+
+    // files to watch for changes made by other programs
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.tabs
+            .iter()
+            .filter_map(|tab| tab.file_path.clone())
+            .collect()
+    }
+
+    // text of every open file, searches read these instead of what is on disk
+    pub fn texts(&self) -> HashMap<PathBuf, String> {
+        self.tabs
+            .iter()
+            .filter_map(|tab| Some((tab.file_path.clone()?, tab.text())))
+            .collect()
+    }
+
+    pub fn active_path(&self) -> Option<PathBuf> {
+        self.tabs[self.active?].file_path.clone()
+    }
+
     pub fn position(&self, path: PathBuf) -> Option<usize> {
         self.tabs.iter().position(|x| {
             if let Some(x_path) = &x.file_path {
@@ -106,7 +197,12 @@ impl TabView {
             .iter()
             .fold(TabBar::new(Message::TabSelected), |tab_bar, tab| {
                 let idx = tab_bar.size();
-                tab_bar.push(idx, iced_aw::TabLabel::Text(tab.get_name().to_owned()))
+                let name = if tab.is_dirty() {
+                    format!("{} ●", tab.get_name())
+                } else {
+                    tab.get_name()
+                };
+                tab_bar.push(idx, iced_aw::TabLabel::Text(name))
             })
             .on_close(Message::TabClose)
             .width(Length::Shrink)
@@ -133,12 +229,35 @@ impl TabView {
 pub struct Search {
     id: text_input::Id,
     text: String,
+    options: search::Options,
+    matches: Vec<search::Match>,
+    // index of the selected match
+    current: Option<usize>,
+    error: Option<String>,
+    replace: String,
+    // selection captured when searching in selection
+    scope: Option<(Cursor, Cursor)>,
+}
+
+struct Conflict {
+    // lines of the diff from the buffer to the file, when shown
+    diff: Option<Vec<(ChangeTag, String)>>,
 }
 
 pub struct Tab {
     pub file_path: Option<PathBuf>,
+    // number in the name of a buffer that was never saved
+    untitled: Option<usize>,
 
     editor: RwLock<SyntaxEditor<'static, 'static>>, // RwLock allows writing during draw
+    history: RwLock<History>,
+    // mode and registers of the vim profile
+    vim: RwLock<text_box::Vim>,
+    backup: file::Backup,
+    // hash of the file as last read or written, to tell other programs' changes from ours
+    disk_hash: u64,
+    // the file changed on disk while the tab had unsaved changes
+    conflict: Option<Conflict>,
     attrs: Attrs<'static>,
     metrics: Metrics,
     text_box_id: iced::advanced::widget::Id,
@@ -147,7 +266,7 @@ pub struct Tab {
 }
 
 impl Tab {
-    fn new() -> Self {
+    fn new(undo_limit: usize, backup: file::Backup) -> Self {
         let metrics = Metrics::new(14.0, 20.0);
         let buffer = Buffer::new_empty(metrics);
         let attrs = Attrs::new().family(cosmic_text::Family::Monospace);
@@ -156,12 +275,24 @@ impl Tab {
 
         let mut tab = Self {
             file_path: None,
+            untitled: None,
             editor: RwLock::new(editor),
+            history: RwLock::new(History::new(undo_limit)),
+            vim: RwLock::new(text_box::Vim::new()),
+            backup,
+            disk_hash: 0,
+            conflict: None,
             attrs,
             metrics,
             search: Search {
                 id: text_input::Id::unique(),
                 text: "".to_string(),
+                options: search::Options::default(),
+                matches: Vec::new(),
+                current: None,
+                error: None,
+                replace: "".to_string(),
+                scope: None,
             },
             search_open: false,
             text_box_id: advanced::widget::Id::unique(),
@@ -183,28 +314,231 @@ impl Tab {
         });
 
         editor.load_text(file_path.clone(), self.attrs.clone())?;
+        self.disk_hash = history::fnv_hash(&fs::read(&file_path)?);
+        if let Err(err) = self.history.write().unwrap().restore(&file_path) {
+            log::warn!(
+                "could not restore history of {}: {}",
+                file_path.display(),
+                err
+            );
+        }
         self.file_path = Some(file_path);
         Ok(())
     }
 
+    // buffer differs from what was last loaded or saved
+    pub fn is_dirty(&self) -> bool {
+        !self.history.read().unwrap().is_saved()
+    }
+
+    // keeps the undo history for the next time the file is opened
+    pub fn store_history(&self) {
+        if let Some(path) = &self.file_path
+            && let Err(err) = self.history.read().unwrap().store(path)
+        {
+            log::warn!("could not store history of {}: {}", path.display(), err);
+        }
+    }
+
+    // untitled buffers have nowhere to go, see save_as
     pub fn save(&mut self) -> io::Result<()> {
+        if let Some(path) = self.file_path.clone() {
+            self.write(&path)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, path: PathBuf) -> io::Result<()> {
+        self.write(&path)?;
+        let path = fs::canonicalize(&path)?;
+
+        // highlighting follows the new extension
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let mut editor = self.editor.write().unwrap();
+        editor.syntax_by_extension(extension);
+        editor.set_redraw(true);
+
+        self.file_path = Some(path);
+        self.untitled = None;
+        Ok(())
+    }
+
+    // the buffer as it would be saved
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.editor.read().unwrap().with_buffer(|buf| {
+            for line in buf.lines.iter() {
+                text.push_str(line.text());
+                text.push_str(line.ending().as_str());
+            }
+        });
+        text
+    }
+
+    fn write(&mut self, path: &Path) -> io::Result<()> {
+        let text = self.text();
+        file::save(path, text.as_bytes(), self.backup)?;
+        self.disk_hash = history::fnv_hash(text.as_bytes());
+        self.history.write().unwrap().mark_saved();
+        Ok(())
+    }
+
+    // the watched file changed, a clean tab follows it, a dirty one asks first
+    pub fn file_changed(&mut self) -> io::Result<()> {
+        let Some(path) = &self.file_path else {
+            return Ok(());
+        };
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            // removed, or about to be renamed over
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        // our own save, or only touched
+        if history::fnv_hash(&contents) == self.disk_hash {
+            return Ok(());
+        }
+
+        if self.is_dirty() {
+            if self.conflict.is_none() {
+                self.conflict = Some(Conflict { diff: None });
+            }
+            Ok(())
+        } else {
+            self.reload()
+        }
+    }
+
+    // reads the file again as one undoable change, so the version before can be
+    // had back. the cursor and scroll stay where they were
+    pub fn reload(&mut self) -> io::Result<()> {
+        let Some(path) = self.file_path.clone() else {
+            return Ok(());
+        };
+        let contents = fs::read(&path)?;
+        let text = String::from_utf8_lossy(&contents);
+        // lines split like a load splits them, insert_at only knows \n
+        let (lines, mut endings): (Vec<&str>, Vec<LineEnding>) = LineIter::new(&text)
+            .map(|(range, ending)| (&text[range], ending))
+            .unzip();
+        if endings.is_empty() {
+            endings.push(LineEnding::default());
+        }
+
+        let mut font_system = FONT_SYSTEM.get().unwrap().write().unwrap();
+        let mut guard = self.editor.write().unwrap();
+        let mut editor = guard.borrow_with(&mut font_system);
+
+        let mut cursor = editor.cursor();
+        let scroll = editor.with_buffer(|buffer| buffer.scroll());
+        let end = editor.with_buffer(|buffer| {
+            let last = buffer.lines.len().saturating_sub(1);
+            Cursor::new(
+                last,
+                buffer.lines.get(last).map_or(0, |line| line.text().len()),
+            )
+        });
+        editor.start_change();
+        editor.delete_range(Cursor::new(0, 0), end);
+        editor.insert_at(Cursor::new(0, 0), &lines.join("\n"), None);
+        if let Some(change) = editor.finish_change() {
+            let mut history = self.history.write().unwrap();
+            history.push(change, history::Kind::Other);
+            history.mark_saved();
+        }
+        editor.with_buffer_mut(|buffer| {
+            for (line, ending) in buffer.lines.iter_mut().zip(endings) {
+                line.set_ending(ending);
+            }
+            buffer.set_scroll(scroll);
+            cursor.line = cursor.line.min(buffer.lines.len().saturating_sub(1));
+            let text = buffer.lines[cursor.line].text();
+            cursor.index = cursor.index.min(text.len());
+            while !text.is_char_boundary(cursor.index) {
+                cursor.index -= 1;
+            }
+        });
+        editor.set_selection(Selection::None);
+        editor.set_cursor(cursor);
+
+        drop(guard);
+        drop(font_system);
+
+        self.disk_hash = history::fnv_hash(&contents);
+        self.conflict = None;
+        self.on_edit();
+        Ok(())
+    }
+
+    // keeps the buffer, saving it will overwrite the other change
+    pub fn keep_mine(&mut self) -> io::Result<()> {
         if let Some(path) = &self.file_path {
-            let mut text = String::new();
-            self.editor.write().unwrap().with_buffer(|buf| {
-                for line in buf.lines.iter() {
-                    text.push_str(line.text());
-                    text.push_str(line.ending().as_str());
-                }
-            });
+            self.disk_hash = history::fnv_hash(&fs::read(path)?);
+        }
+        self.history.write().unwrap().forget_saved();
+        self.conflict = None;
+        Ok(())
+    }
+
+    // shows how the file on disk differs from the buffer
+    pub fn toggle_conflict_diff(&mut self) -> io::Result<()> {
+        let Some(path) = &self.file_path else {
+            return Ok(());
+        };
+        let Some(conflict) = &mut self.conflict else {
+            return Ok(());
+        };
+        if conflict.diff.is_some() {
+            conflict.diff = None;
+            return Ok(());
+        }
+
+        let disk = fs::read_to_string(path)?;
+        let mut mine = String::new();
+        self.editor.write().unwrap().with_buffer(|buf| {
+            for line in buf.lines.iter() {
+                mine.push_str(line.text());
+                mine.push_str(line.ending().as_str());
+            }
+        });
 
-            return fs::write(path, text);
+        let diff = TextDiff::from_lines(&mine, &disk);
+        let mut lines = Vec::new();
+        for hunk in diff.unified_diff().context_radius(2).iter_hunks() {
+            lines.push((ChangeTag::Equal, hunk.header().to_string()));
+            for change in hunk.iter_changes() {
+                let line = change.value().trim_end_matches(['\r', '\n']).to_string();
+                lines.push((change.tag(), line));
+            }
         }
+        conflict.diff = Some(lines);
         Ok(())
     }
 
     pub fn search_open(&mut self, text: Option<String>) -> Task<Message> {
         if let Some(text) = text {
             self.search.text = text;
+            // incremental search, jump to the first match while typing
+            self.search_update(true);
+        } else {
+            // seed the query with a single line selection, search inside a multi line one
+            let editor = self.editor.read().unwrap();
+            let bounds = editor.selection_bounds();
+            let selection = editor.copy_selection();
+            drop(editor);
+            // a scope from an earlier search is gone, only a new selection sets one
+            self.clear_scope();
+            match (bounds, selection) {
+                (Some((start, end)), _) if start.line != end.line => {
+                    self.search.options.in_selection = true;
+                    self.search.scope = Some((start, end));
+                }
+                (_, Some(selection)) if !selection.is_empty() => {
+                    self.search.text = selection;
+                }
+                _ => {}
+            }
+            self.search_update(false);
         }
         // note: text seach is a good example of how events flow
         // also: editor is a good example of how leaf nodes work (widgets)
@@ -214,12 +548,275 @@ impl Tab {
 
     pub fn search_close(&mut self) -> Task<Message> {
         self.search_open = false;
+        self.clear_scope();
+        self.redraw();
+        self.focus()
+    }
+
+    pub fn focus(&self) -> Task<Message> {
         // lifesaver: https://jl710.github.io/iced-guide/widget_api/operations.html
         operate(advanced::widget::operation::focusable::focus(
             self.text_box_id.clone(),
         ))
     }
 
+    pub fn search_toggle(&mut self, toggle: search::Toggle) {
+        self.search.options.toggle(toggle);
+        if toggle == search::Toggle::InSelection {
+            self.search.scope = if self.search.options.in_selection {
+                self.editor.read().unwrap().selection_bounds()
+            } else {
+                None
+            };
+            self.search.options.in_selection = self.search.scope.is_some();
+        }
+        self.search_update(true);
+    }
+
+    pub fn replace_text(&mut self, text: String) {
+        self.search.replace = text;
+    }
+
+    // replaces the selected match and selects the next one
+    pub fn replace_next(&mut self) {
+        self.search_update(false);
+        let Some(current) = self.search.current else {
+            self.search_next();
+            return;
+        };
+
+        self.replace_matches(&[self.search.matches[current]]);
+        self.search_next();
+    }
+
+    pub fn replace_all(&mut self) {
+        self.search_update(false);
+        if self.search.matches.is_empty() {
+            return;
+        }
+
+        let matches = self.search.matches.clone();
+        self.replace_matches(&matches);
+    }
+
+    // replaces matches as a single undo step
+    fn replace_matches(&mut self, matches: &[search::Match]) {
+        let options = self.search.options;
+        let Ok(regex) = search::build(&self.search.text, options) else {
+            return;
+        };
+
+        // computed up front, so \b and captures see the original line
+        let replacements: Vec<(search::Match, String)> =
+            self.editor.read().unwrap().with_buffer(|buffer| {
+                matches
+                    .iter()
+                    .map(|found| {
+                        let text = buffer.lines[found.line].text();
+                        let replacement =
+                            search::replacement(&regex, text, found, &self.search.replace, options);
+                        (*found, replacement)
+                    })
+                    .collect()
+            });
+
+        self.apply_replacements(&replacements)
+    }
+
+    // replacements found outside of the buffer, e.g. by the project search
+    // skips lines that no longer read the same as when they were searched, returns
+    // how many were skipped
+    pub fn replace(&mut self, replacements: &[search::Replacement]) -> usize {
+        let total = replacements.len();
+        let replacements: Vec<(search::Match, String)> =
+            self.editor.read().unwrap().with_buffer(|buffer| {
+                replacements
+                    .iter()
+                    .filter(|replacement| {
+                        buffer
+                            .lines
+                            .get(replacement.found.line)
+                            .is_some_and(|line| line.text() == replacement.line)
+                    })
+                    .map(|replacement| (replacement.found, replacement.text.clone()))
+                    .collect()
+            });
+        let skipped = total - replacements.len();
+        if !replacements.is_empty() {
+            self.apply_replacements(&replacements);
+        }
+        skipped
+    }
+
+    // replacements must be sorted by position
+    fn apply_replacements(&mut self, replacements: &[(search::Match, String)]) {
+        let mut editor = self.editor.write().unwrap();
+        editor.start_change();
+        let mut cursor = editor.cursor();
+        // back to front, so earlier matches keep their indices
+        for (found, replacement) in replacements.iter().rev() {
+            let start = Cursor::new(found.line, found.start);
+            editor.delete_range(start, Cursor::new(found.line, found.end));
+            cursor = editor.insert_at(start, replacement, None);
+
+            // matches are on a single line, only the end of the scope can shift
+            if let Some((_, scope_end)) = &mut self.search.scope
+                && scope_end.line == found.line
+                && found.end <= scope_end.index
+            {
+                scope_end.index = scope_end.index + replacement.len() - (found.end - found.start);
+            }
+        }
+        editor.set_selection(Selection::None);
+        editor.set_cursor(cursor);
+        if let Some(change) = editor.finish_change() {
+            self.history
+                .write()
+                .unwrap()
+                .push(change, history::Kind::Other);
+        }
+        drop(editor);
+
+        // the scope was shifted along, unlike on other edits
+        if self.search_open {
+            self.search_update(false);
+        }
+    }
+
+    pub fn search_next(&mut self) {
+        self.search_step(true);
+    }
+
+    pub fn search_previous(&mut self) {
+        self.search_step(false);
+    }
+
+    // called when the text box changed the buffer. the scope's cursors don't follow
+    // edits, it is dropped rather than searched at the wrong place
+    pub fn on_edit(&mut self) {
+        self.clear_scope();
+        if self.search_open {
+            self.search_update(false);
+        }
+    }
+
+    fn clear_scope(&mut self) {
+        self.search.scope = None;
+        self.search.options.in_selection = false;
+    }
+
+    // finds all matches, select moves the selection to the first match after the cursor
+    fn search_update(&mut self, select: bool) {
+        let mut editor = self.editor.write().unwrap();
+        editor.set_redraw(true);
+
+        self.search.matches.clear();
+        self.search.current = None;
+        self.search.error = None;
+        if self.search.text.is_empty() {
+            return;
+        }
+
+        let regex = match search::build(&self.search.text, self.search.options) {
+            Ok(ok) => ok,
+            Err(err) => {
+                log::debug!("invalid search pattern: {}", err);
+                self.search.error = Some("Invalid pattern".to_string());
+                return;
+            }
+        };
+        editor.with_buffer(|buffer| {
+            for (line_i, line) in buffer.lines.iter().enumerate() {
+                search::find_in_line(&regex, line_i, line.text(), &mut self.search.matches);
+            }
+        });
+        if let Some((start, end)) = self.search.scope {
+            self.search.matches.retain(|found| {
+                (found.line, found.start) >= (start.line, start.index)
+                    && (found.line, found.end) <= (end.line, end.index)
+            });
+        }
+
+        if select {
+            // anchor on the selection start, so a growing query keeps its match
+            let anchor = editor
+                .selection_bounds()
+                .map_or(editor.cursor(), |(start, _)| start);
+            let index = self
+                .search
+                .matches
+                .iter()
+                .position(|found| (found.line, found.start) >= (anchor.line, anchor.index))
+                .or(if self.search.matches.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                });
+            if let Some(index) = index {
+                select_match(&mut editor, self.search.matches[index]);
+                self.search.current = Some(index);
+            }
+        } else {
+            self.search.current = current_match(&editor, &self.search.matches);
+        }
+    }
+
+    fn search_step(&mut self, forward: bool) {
+        self.search_update(false);
+        let len = self.search.matches.len();
+        if len == 0 {
+            return;
+        }
+
+        let mut editor = self.editor.write().unwrap();
+        let index = match self.search.current {
+            Some(current) if forward => (current + 1) % len,
+            Some(current) => (current + len - 1) % len,
+            None => {
+                // nothing selected, step from the cursor
+                let cursor = editor.cursor();
+                let after = self
+                    .search
+                    .matches
+                    .iter()
+                    .position(|found| (found.line, found.start) >= (cursor.line, cursor.index));
+                match after {
+                    Some(after) if forward => after,
+                    Some(after) => (after + len - 1) % len,
+                    None if forward => 0,
+                    None => len - 1,
+                }
+            }
+        };
+        select_match(&mut editor, self.search.matches[index]);
+        self.search.current = Some(index);
+    }
+
+    // moves the cursor, clamped to the buffer
+    pub fn goto(&mut self, cursor: Cursor) {
+        let mut editor = self.editor.write().unwrap();
+        let cursor = editor.with_buffer(|buffer| {
+            let line = cursor.line.min(buffer.lines.len().saturating_sub(1));
+            let text = buffer.lines.get(line).map_or("", |line| line.text());
+            let mut index = cursor.index.min(text.len());
+            while !text.is_char_boundary(index) {
+                index -= 1;
+            }
+            Cursor::new(line, index)
+        });
+        editor.set_selection(Selection::None);
+        editor.set_cursor(cursor);
+    }
+
+    // a char column, like one typed by the user
+    pub fn goto_column(&mut self, line: usize, column: usize) {
+        let index = self.editor.read().unwrap().with_buffer(|buffer| {
+            let text = buffer.lines.get(line).map_or("", |line| line.text());
+            text_box::char_index(text, column)
+        });
+        self.goto(Cursor::new(line, index));
+    }
+
     pub fn scroll(&mut self, scroll: f32) {
         let mut editor = self.editor.write().unwrap();
         editor.with_buffer_mut(|buffer| {
@@ -231,29 +828,132 @@ impl Tab {
 
     pub fn view(&self) -> Column<Message, theme::MyTheme> {
         let mut col = Column::new();
+        let mut text_box = text_box::text_box(&self.editor, &self.history, self.metrics);
         if self.search_open {
+            let options = self.search.options;
+            let toggle = |label, toggle, active| {
+                search::toggle_button(label, active, Message::TabSearchToggle(toggle))
+            };
+            let status = if let Some(error) = &self.search.error {
+                error.clone()
+            } else if self.search.text.is_empty() {
+                String::new()
+            } else if self.search.matches.is_empty() {
+                "No results".to_string()
+            } else if let Some(current) = self.search.current {
+                format!("{} of {}", current + 1, self.search.matches.len())
+            } else {
+                format!("{} results", self.search.matches.len())
+            };
+
             col = col.push(
-                text_input("Find Something...", &self.search.text)
-                    .on_input(Message::TabSearch)
-                    .id(self.search.id.clone()),
-            )
+                Row::new()
+                    .push(
+                        text_input("Find Something...", &self.search.text)
+                            .on_input(Message::TabSearch)
+                            .on_submit(Message::TabSearchSubmit)
+                            .id(self.search.id.clone()),
+                    )
+                    .push(toggle(
+                        "Aa",
+                        search::Toggle::CaseSensitive,
+                        options.case_sensitive,
+                    ))
+                    .push(toggle("ab", search::Toggle::WholeWord, options.whole_word))
+                    .push(toggle(".*", search::Toggle::Regex, options.regex))
+                    .push(toggle(
+                        "Sel",
+                        search::Toggle::InSelection,
+                        options.in_selection,
+                    ))
+                    .push(text(status).width(Length::Fixed(100.0)))
+                    .align_y(Alignment::Center)
+                    .spacing(4.0),
+            );
+            col = col.push(
+                Row::new()
+                    .push(
+                        text_input("Replace...", &self.search.replace)
+                            .on_input(Message::TabReplace)
+                            .on_submit(Message::TabReplaceNext),
+                    )
+                    .push(toggle(
+                        "AB",
+                        search::Toggle::PreserveCase,
+                        options.preserve_case,
+                    ))
+                    .push(button(text("Replace")).on_press(Message::TabReplaceNext))
+                    .push(button(text("All")).on_press(Message::TabReplaceAll))
+                    .push(text("").width(Length::Fixed(100.0)))
+                    .align_y(Alignment::Center)
+                    .spacing(4.0),
+            );
+            text_box = text_box.matches(&self.search.matches);
         }
 
+        if let Some(conflict) = &self.conflict {
+            col = col.push(
+                widget::container(
+                    Row::new()
+                        .push(text("The file changed on disk.").width(Length::Fill))
+                        .push(button(text("Reload")).on_press(Message::TabConflictReload))
+                        .push(button(text("Keep Mine")).on_press(Message::TabConflictKeep))
+                        .push(button(text("Diff")).on_press(Message::TabConflictDiff))
+                        .align_y(Alignment::Center)
+                        .spacing(4.0),
+                )
+                .padding(5)
+                .style(theme::container::error),
+            );
+            if let Some(diff) = &conflict.diff {
+                // mine is removed, the file on disk is added
+                let lines = diff.iter().map(|(tag, line)| {
+                    let (sign, style): (_, fn(&theme::MyTheme) -> _) = match tag {
+                        ChangeTag::Delete => ("- ", theme::text::removed),
+                        ChangeTag::Insert => ("+ ", theme::text::added),
+                        ChangeTag::Equal => ("  ", theme::text::none),
+                    };
+                    text(format!("{}{}", sign, line)).style(style).into()
+                });
+                col = col.push(
+                    scrollable(Column::with_children(lines))
+                        .width(Length::Fill)
+                        .height(Length::Fixed(200.0)),
+                );
+            }
+        }
+
+        let vim = keymap().profile == Profile::Vim;
+        if vim {
+            text_box = text_box.vim(&self.vim);
+        }
         // TODO: halloy's combo_box
-        col.push(text_box::text_box(&self.editor, self.metrics).id(self.text_box_id.clone()))
+        col = col.push(text_box.id(self.text_box_id.clone()));
+        if vim {
+            let (status, keys) = self.vim.read().unwrap().status();
+            col = col.push(
+                Row::new()
+                    .push(text(status).width(Length::Fill))
+                    .push(text(keys))
+                    .padding([2, 5]),
+            );
+        }
+        col
     }
 
     pub fn redraw(&self) {
         self.editor.write().unwrap().set_redraw(true);
     }
 
-    fn get_name(&self) -> String {
+    pub fn get_name(&self) -> String {
         if let Some(path) = &self.file_path {
             path.file_name()
                 .expect("invalid file name")
                 .to_str()
                 .expect("could not parse to string")
                 .to_owned()
+        } else if let Some(number) = self.untitled {
+            format!("Untitled-{}", number)
         } else {
             "New Tab".into()
         }
@@ -271,3 +971,23 @@ impl Tab {
         });
     }
 }
+
+fn select_match(editor: &mut SyntaxEditor<'static, 'static>, found: search::Match) {
+    editor.set_selection(Selection::Normal(Cursor::new(found.line, found.start)));
+    // moving the cursor scrolls the match into view on the next draw
+    editor.set_cursor(Cursor::new(found.line, found.end));
+}
+
+// match that is exactly selected in the editor
+fn current_match(
+    editor: &SyntaxEditor<'static, 'static>,
+    matches: &[search::Match],
+) -> Option<usize> {
+    let (start, end) = editor.selection_bounds()?;
+    if start.line != end.line {
+        return None;
+    }
+    matches.iter().position(|found| {
+        found.line == start.line && found.start == start.index && found.end == end.index
+    })
+}
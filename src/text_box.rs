@@ -1,11 +1,21 @@
 use std::sync::RwLock;
 
 use cosmic_text::{Metrics, SyntaxEditor};
+
+use crate::history::History;
+mod cursors;
+mod kill_ring;
 mod text_box;
+mod vim;
+
+pub use cursors::char_index;
+pub use text_box::{Binding, BindingMotion};
+pub use vim::Vim;
 
 pub fn text_box<'a>(
     editor: &'a RwLock<SyntaxEditor<'static, 'static>>,
+    history: &'a RwLock<History>,
     metrics: Metrics,
 ) -> text_box::TextBox<'a> {
-    text_box::TextBox::new(editor, metrics)
+    text_box::TextBox::new(editor, history, metrics)
 }
@@ -0,0 +1,220 @@
+use cosmic_text::{Buffer, ChangeItem, Cursor, Selection};
+
+// a cursor next to the editor's own, which is drawn and edited like it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Caret {
+    pub cursor: Cursor,
+    pub selection: Selection,
+    // x kept while moving up and down, like the editor's cursor_x_opt
+    pub cursor_x: Option<i32>,
+}
+
+impl Caret {
+    pub fn new(cursor: Cursor, selection: Selection) -> Self {
+        Self {
+            cursor,
+            selection,
+            cursor_x: None,
+        }
+    }
+
+    // selected range, or the cursor itself
+    pub fn bounds(&self) -> (Cursor, Cursor) {
+        match self.selection {
+            Selection::Normal(anchor) | Selection::Line(anchor) | Selection::Word(anchor) => {
+                if position(&anchor) < position(&self.cursor) {
+                    (anchor, self.cursor)
+                } else {
+                    (self.cursor, anchor)
+                }
+            }
+            Selection::None => (self.cursor, self.cursor),
+        }
+    }
+
+    // moves the caret along with an edit made elsewhere
+    pub fn transform(&mut self, item: &ChangeItem) {
+        transform(&mut self.cursor, item);
+        match &mut self.selection {
+            Selection::Normal(anchor) | Selection::Line(anchor) | Selection::Word(anchor) => {
+                transform(anchor, item)
+            }
+            Selection::None => {}
+        }
+    }
+
+    // keeps the caret inside the buffer, it may have been edited from outside
+    pub fn clamp(&mut self, buffer: &Buffer) {
+        clamp(&mut self.cursor, buffer);
+        match &mut self.selection {
+            Selection::Normal(anchor) | Selection::Line(anchor) | Selection::Word(anchor) => {
+                clamp(anchor, buffer)
+            }
+            Selection::None => {}
+        }
+    }
+}
+
+// affinity is ignored, carets at the same index are the same caret
+pub fn position(cursor: &Cursor) -> (usize, usize) {
+    (cursor.line, cursor.index)
+}
+
+fn transform(cursor: &mut Cursor, item: &ChangeItem) {
+    let (start, end) = (item.start, item.end);
+    if position(cursor) < position(&start) {
+        return;
+    }
+
+    if item.insert {
+        if cursor.line == start.line {
+            cursor.index = end.index + (cursor.index - start.index);
+        }
+        cursor.line += end.line - start.line;
+    } else if position(cursor) < position(&end) {
+        // inside the deleted range
+        cursor.line = start.line;
+        cursor.index = start.index;
+    } else {
+        if cursor.line == end.line {
+            cursor.index = start.index + (cursor.index - end.index);
+        }
+        cursor.line -= end.line - start.line;
+    }
+}
+
+fn clamp(cursor: &mut Cursor, buffer: &Buffer) {
+    let Some(last) = buffer.lines.len().checked_sub(1) else {
+        return;
+    };
+    cursor.line = cursor.line.min(last);
+    let text = buffer.lines[cursor.line].text();
+    cursor.index = cursor.index.min(text.len());
+    while !text.is_char_boundary(cursor.index) {
+        cursor.index -= 1;
+    }
+}
+
+// sorted by position, one caret per position
+pub fn dedup(carets: &mut Vec<Caret>) {
+    carets.sort_by_key(|caret| position(&caret.cursor));
+    carets.dedup_by_key(|caret| position(&caret.cursor));
+}
+
+// every occurrence of text in the buffer as (start, end), text may span lines
+pub fn occurrences(buffer: &Buffer, text: &str) -> Vec<(Cursor, Cursor)> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    let mut content = String::new();
+    // byte offset each line starts at in content
+    let mut starts = Vec::with_capacity(buffer.lines.len());
+    for (line_i, line) in buffer.lines.iter().enumerate() {
+        if line_i > 0 {
+            content.push('\n');
+        }
+        starts.push(content.len());
+        content.push_str(line.text());
+    }
+
+    let cursor_at = |offset: usize| {
+        let line = starts.partition_point(|start| *start <= offset) - 1;
+        Cursor::new(line, offset - starts[line])
+    };
+    content
+        .match_indices(text)
+        .map(|(offset, found)| (cursor_at(offset), cursor_at(offset + found.len())))
+        .collect()
+}
+
+// rectangular selection, positions are (line, column) with columns counted in chars
+// and allowed past the end of a line. columns line up in monospace text only, a tab
+// counts as one column however wide it is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Block {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+impl Block {
+    pub fn new(at: (usize, usize)) -> Self {
+        Self {
+            anchor: at,
+            head: at,
+        }
+    }
+
+    pub fn lines(&self) -> (usize, usize) {
+        (
+            self.anchor.0.min(self.head.0),
+            self.anchor.0.max(self.head.0),
+        )
+    }
+
+    pub fn columns(&self) -> (usize, usize) {
+        (
+            self.anchor.1.min(self.head.1),
+            self.anchor.1.max(self.head.1),
+        )
+    }
+
+    // a caret per line, selecting the part of the line inside the block.
+    // the first caret is on the head line, short lines get a caret at their end
+    pub fn carets(&self, buffer: &Buffer) -> Vec<Caret> {
+        let (top, bottom) = self.lines();
+        let (left, right) = self.columns();
+        let bottom = bottom.min(buffer.lines.len().saturating_sub(1));
+
+        let mut carets = Vec::new();
+        for line in top..=bottom {
+            let text = buffer.lines[line].text();
+            let start = Cursor::new(line, char_index(text, left));
+            let end = Cursor::new(line, char_index(text, right));
+            // the cursor sits on the side the head moved to
+            let (cursor, anchor) = if self.head.1 >= self.anchor.1 {
+                (end, start)
+            } else {
+                (start, end)
+            };
+            let selection = if start == end {
+                Selection::None
+            } else {
+                Selection::Normal(anchor)
+            };
+            let caret = Caret::new(cursor, selection);
+            if line == self.head.0 {
+                carets.insert(0, caret);
+            } else {
+                carets.push(caret);
+            }
+        }
+        carets
+    }
+
+    // the block as lines of text, short lines copy as empty lines
+    pub fn text(&self, buffer: &Buffer) -> String {
+        let (top, bottom) = self.lines();
+        let (left, right) = self.columns();
+        let bottom = bottom.min(buffer.lines.len().saturating_sub(1));
+
+        let mut lines = Vec::new();
+        for line in top..=bottom {
+            let text = buffer.lines[line].text();
+            lines.push(&text[char_index(text, left)..char_index(text, right)]);
+        }
+        lines.join("\n")
+    }
+}
+
+// byte index of a char column, the end of the text if the line is shorter
+pub fn char_index(text: &str, column: usize) -> usize {
+    text.char_indices()
+        .nth(column)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+// char column of a byte index
+pub fn column(text: &str, index: usize) -> usize {
+    text[..index.min(text.len())].chars().count()
+}
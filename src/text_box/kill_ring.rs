@@ -0,0 +1,61 @@
+use std::{
+    collections::VecDeque,
+    sync::{LazyLock, Mutex},
+};
+
+// kills kept, older ones are dropped
+const MAX_KILLS: usize = 120;
+
+// shared by all editors, like in emacs
+pub static KILL_RING: LazyLock<Mutex<KillRing>> = LazyLock::new(|| Mutex::new(KillRing::new()));
+
+// text killed or copied, most recent first. a yank takes the front, yank-pop
+// replaces it with the kills before
+pub struct KillRing {
+    kills: VecDeque<String>,
+    // the kill last yanked
+    yank: usize,
+}
+
+impl KillRing {
+    fn new() -> Self {
+        Self {
+            kills: VecDeque::new(),
+            yank: 0,
+        }
+    }
+
+    // kills in a row append to the front kill. returns the front kill, for the clipboard
+    pub fn kill(&mut self, text: String, append: bool) -> String {
+        match self.kills.front_mut() {
+            Some(front) if append => front.push_str(&text),
+            _ => {
+                self.kills.push_front(text);
+                self.kills.truncate(MAX_KILLS);
+            }
+        }
+        self.yank = 0;
+        self.kills[0].clone()
+    }
+
+    // text copied in another program since the last kill is yanked first
+    pub fn yank(&mut self, clipboard: Option<String>) -> Option<String> {
+        if let Some(text) = clipboard
+            && !text.is_empty()
+            && self.kills.front() != Some(&text)
+        {
+            self.kill(text, false);
+        }
+        self.yank = 0;
+        self.kills.front().cloned()
+    }
+
+    // the kill before the one last yanked, wrapping around to the front
+    pub fn pop(&mut self) -> Option<String> {
+        if self.kills.is_empty() {
+            return None;
+        }
+        self.yank = (self.yank + 1) % self.kills.len();
+        self.kills.get(self.yank).cloned()
+    }
+}
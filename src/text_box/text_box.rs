@@ -1,4 +1,7 @@
-use cosmic_text::{Attrs, AttrsList, BufferLine, Edit, LineEnding, Metrics, Motion, SyntaxEditor};
+use cosmic_text::{
+    Attrs, AttrsList, BorrowedWithFontSystem, BufferLine, Edit, LineEnding, Metrics, Motion,
+    Selection, SyntaxEditor,
+};
 use iced::{
     Element, Length, Padding, Rectangle, Size,
     advanced::{
@@ -15,13 +18,33 @@ use std::{
     time::{self, Instant},
 };
 
-use crate::{Message, font_system, swash_cache};
+use super::{
+    cursors::{self, Caret},
+    kill_ring::KILL_RING,
+    vim::{Effect, Input, Shape, Vim},
+};
+use crate::history::{self, History};
+use crate::{
+    Message,
+    commands::Action,
+    font_system,
+    key_binds::{KeyBind, Press},
+    keymap, search, swash_cache,
+};
+
+pub(super) type Editor<'a> = BorrowedWithFontSystem<'a, SyntaxEditor<'static, 'static>>;
 
 // widget vars for settings & input, state vars for generated state
 pub struct TextBox<'a> {
     id: Option<Id>,
     editor: &'a RwLock<SyntaxEditor<'static, 'static>>,
+    // outlives the widget state, which iced may rebuild
+    history: &'a RwLock<History>,
     metrics: Metrics,
+    // search matches to highlight, sorted by position
+    matches: &'a [search::Match],
+    // modal editing in front of the keymap, if the vim profile is on
+    vim: Option<&'a RwLock<Vim>>,
 
     // time between clicks for ClickKind.
     click_timing: time::Duration,
@@ -40,11 +63,18 @@ enum ClickKind {
 }
 
 impl<'a> TextBox<'a> {
-    pub fn new(editor: &'a RwLock<SyntaxEditor<'static, 'static>>, metrics: Metrics) -> Self {
+    pub fn new(
+        editor: &'a RwLock<SyntaxEditor<'static, 'static>>,
+        history: &'a RwLock<History>,
+        metrics: Metrics,
+    ) -> Self {
         Self {
             id: None,
             editor,
+            history,
             metrics,
+            matches: &[],
+            vim: None,
             click_timing: time::Duration::from_millis(500),
             auto_scroll: None,
             line_number: true,
@@ -60,17 +90,329 @@ impl<'a> TextBox<'a> {
         self
     }
 
-    fn finish_change(&self, editor: &mut SyntaxEditor<'static, 'static>, state: &mut State) {
-        if state.redo_buffer.len() > 0 {
-            state.redo_buffer.clear();
+    pub fn matches(mut self, matches: &'a [search::Match]) -> Self {
+        self.matches = matches;
+        self
+    }
+
+    pub fn vim(mut self, vim: &'a RwLock<Vim>) -> Self {
+        self.vim = Some(vim);
+        self
+    }
+
+    fn record(&self, change: Option<cosmic_text::Change>, kind: history::Kind) {
+        if let Some(change) = change {
+            self.history
+                .write()
+                .expect("history not writable")
+                .push(change, kind);
+        }
+    }
+
+    // runs f at the editor cursor and at every extra caret, last caret first so
+    // earlier positions stay valid. f gets None for the editor cursor and the
+    // caret's own x otherwise. returns the edits at all carets as one change.
+    fn each_caret<F>(
+        &self,
+        editor: &mut Editor<'_>,
+        state: &mut State,
+        mut f: F,
+    ) -> Option<cosmic_text::Change>
+    where
+        F: FnMut(&mut Editor<'_>, Option<&mut Option<i32>>),
+    {
+        if state.carets.is_empty() {
+            editor.start_change();
+            f(editor, None);
+            return editor.finish_change();
+        }
+
+        editor.with_buffer(|buffer| {
+            for caret in state.carets.iter_mut() {
+                caret.clamp(buffer);
+            }
+        });
+        let mut carets: Vec<(bool, Caret)> =
+            state.carets.drain(..).map(|caret| (false, caret)).collect();
+        carets.push((true, Caret::new(editor.cursor(), editor.selection())));
+        carets.sort_by_key(|(_, caret)| cmp::Reverse(cursors::position(&caret.bounds().0)));
+
+        let mut combined = cosmic_text::Change::default();
+        for i in 0..carets.len() {
+            let (primary, mut caret) = carets[i];
+            editor.set_cursor(caret.cursor);
+            editor.set_selection(caret.selection);
+            editor.start_change();
+            f(editor, (!primary).then_some(&mut caret.cursor_x));
+
+            carets[i].1 = Caret {
+                cursor: editor.cursor(),
+                selection: editor.selection(),
+                ..caret
+            };
+            if let Some(change) = editor.finish_change() {
+                if !change.items.is_empty() {
+                    // the remembered x belongs to the text before the edit
+                    carets[i].1.cursor_x = None;
+                }
+                for item in change.items.iter() {
+                    for (j, (_, other)) in carets.iter_mut().enumerate() {
+                        if j != i {
+                            other.transform(item);
+                        }
+                    }
+                }
+                combined.items.extend(change.items);
+            }
+        }
+
+        let mut primary = None;
+        for (is_primary, caret) in carets {
+            if is_primary {
+                primary = Some(caret);
+            } else {
+                state.carets.push(caret);
+            }
+        }
+        let primary = primary.expect("editor cursor is always a caret");
+        editor.set_cursor(primary.cursor);
+        editor.set_selection(primary.selection);
+        cursors::dedup(&mut state.carets);
+        state
+            .carets
+            .retain(|caret| cursors::position(&caret.cursor) != cursors::position(&primary.cursor));
+
+        editor.set_redraw(true);
+        Some(combined)
+    }
+
+    fn undo(&self, editor: &mut Editor<'_>, state: &mut State) {
+        self.clear_carets(editor, state);
+        let change = self.history.write().expect("history not writable").undo();
+        if let Some(change) = change {
+            editor.apply_change(&change);
+        }
+    }
+
+    fn redo(&self, editor: &mut Editor<'_>, state: &mut State) {
+        self.clear_carets(editor, state);
+        let change = self.history.write().expect("history not writable").redo();
+        if let Some(change) = change {
+            editor.apply_change(&change);
+        }
+    }
+
+    // applies what a key did in vim, true if the text was edited
+    fn vim_effects(
+        &self,
+        editor: &mut Editor<'_>,
+        state: &mut State,
+        effects: Vec<Effect>,
+        shell: &mut iced::advanced::Shell<'_, Message>,
+    ) -> bool {
+        let mut edited = false;
+        for effect in effects {
+            match effect {
+                Effect::Edit(change) => {
+                    edited |= !change.items.is_empty();
+                    self.record(Some(change), history::Kind::Other);
+                }
+                Effect::Undo => {
+                    self.undo(editor, state);
+                    edited = true;
+                }
+                Effect::Redo => {
+                    self.redo(editor, state);
+                    edited = true;
+                }
+                // the block is shown by its carets, vim keeps the editor cursor
+                Effect::Block(Some(block)) => {
+                    let (cursor, selection) = (editor.cursor(), editor.selection());
+                    self.set_block(editor, state, block);
+                    editor.set_cursor(cursor);
+                    editor.set_selection(selection);
+                }
+                Effect::Block(None) => {
+                    if state.block.take().is_some() {
+                        state.carets.clear();
+                    }
+                }
+                Effect::Carets(carets) => {
+                    state.carets = carets;
+                    state.block = None;
+                }
+                Effect::Publish(message) => shell.publish(message),
+            }
+        }
+        shell.publish(Message::VimStatus);
+        editor.set_redraw(true);
+        edited
+    }
+
+    fn clear_carets(&self, editor: &mut Editor<'_>, state: &mut State) {
+        if !state.carets.is_empty() || state.block.is_some() {
+            state.carets.clear();
+            state.block = None;
+            editor.set_redraw(true);
+        }
+    }
+
+    // selects the block with a caret per line, the head line gets the editor cursor
+    fn set_block(&self, editor: &mut Editor<'_>, state: &mut State, block: cursors::Block) {
+        let mut carets = editor.with_buffer(|buffer| block.carets(buffer));
+        if carets.is_empty() {
+            return;
+        }
+        let primary = carets.remove(0);
+        editor.set_cursor(primary.cursor);
+        editor.set_selection(primary.selection);
+        state.carets = carets;
+        cursors::dedup(&mut state.carets);
+        state.block = Some(block);
+        editor.set_redraw(true);
+    }
+
+    // (line, column) under a point in buffer coordinates, columns may be past the line end
+    fn block_position(&self, editor: &Editor<'_>, state: &State, x: f32, y: f32) -> (usize, usize) {
+        let char_width = match state.char_width.get() {
+            // not drawn yet
+            0.0 => self.metrics.font_size * 0.6,
+            char_width => char_width,
+        };
+        let column = (x / char_width).round().max(0.0) as usize;
+        let line = editor.with_buffer(|buffer| {
+            let mut line = buffer.scroll().line;
+            for run in buffer.layout_runs() {
+                if run.line_top > y {
+                    break;
+                }
+                line = run.line_i;
+            }
+            line
+        });
+        (line, column)
+    }
+
+    // selects the word under the cursor, true if there was one
+    fn select_word(&self, editor: &mut Editor<'_>) -> bool {
+        let cursor = editor.cursor();
+        editor.set_selection(Selection::Word(cursor));
+        match editor.selection_bounds() {
+            Some((start, end)) if start != end => {
+                editor.set_selection(Selection::Normal(start));
+                editor.set_cursor(end);
+                true
+            }
+            _ => {
+                editor.set_selection(Selection::None);
+                false
+            }
+        }
+    }
+
+    // selects the next occurrence of the selection with a new caret,
+    // the first press only selects the word under the cursor
+    fn add_next_occurrence(&self, editor: &mut Editor<'_>, state: &mut State) {
+        let Some((start, end)) = editor.selection_bounds() else {
+            self.select_word(editor);
+            return;
+        };
+        let Some(text) = editor.copy_selection() else {
+            return;
+        };
+
+        let occurrences = editor.with_buffer(|buffer| cursors::occurrences(buffer, &text));
+        let taken = |found: &(cosmic_text::Cursor, cosmic_text::Cursor)| {
+            cursors::position(&found.0) == cursors::position(&start)
+                || state.carets.iter().any(|caret| {
+                    cursors::position(&caret.bounds().0) == cursors::position(&found.0)
+                })
+        };
+        // wraps around to the start of the buffer
+        let next = occurrences
+            .iter()
+            .filter(|found| cursors::position(&found.0) >= cursors::position(&end))
+            .chain(occurrences.iter())
+            .find(|found| !taken(found));
+
+        if let Some((next_start, next_end)) = next {
+            state
+                .carets
+                .push(Caret::new(editor.cursor(), editor.selection()));
+            editor.set_selection(Selection::Normal(*next_start));
+            editor.set_cursor(*next_end);
+            editor.set_redraw(true);
+        }
+    }
+
+    // puts a caret on every occurrence of the selection, or of the word under the cursor
+    fn select_all_occurrences(&self, editor: &mut Editor<'_>, state: &mut State) {
+        if editor.selection_bounds().is_none() && !self.select_word(editor) {
+            return;
         }
-        if let Some(change) = editor.finish_change() {
-            state.undo_buffer.push(change);
+        let (Some((start, _)), Some(text)) = (editor.selection_bounds(), editor.copy_selection())
+        else {
+            return;
+        };
+
+        let occurrences = editor.with_buffer(|buffer| cursors::occurrences(buffer, &text));
+        state.carets = occurrences
+            .into_iter()
+            .filter(|(found, _)| cursors::position(found) != cursors::position(&start))
+            .map(|(found_start, found_end)| Caret::new(found_end, Selection::Normal(found_start)))
+            .collect();
+        editor.set_redraw(true);
+    }
+}
+
+// pastes a copied block at the cursor column of this and the following lines,
+// padding short lines and adding lines past the end of the buffer
+pub(super) fn paste_block(editor: &mut Editor<'_>, content: &str) {
+    editor.delete_selection();
+    let cursor = editor.cursor();
+    let column = editor
+        .with_buffer(|buffer| cursors::column(buffer.lines[cursor.line].text(), cursor.index));
+
+    let mut end = cursor;
+    for (i, part) in content.split('\n').enumerate() {
+        let line = cursor.line + i;
+        let (line_count, last_len) = editor.with_buffer(|buffer| {
+            let last = buffer.lines.last().map_or(0, |line| line.text().len());
+            (buffer.lines.len(), last)
+        });
+        if line >= line_count {
+            editor.insert_at(
+                cosmic_text::Cursor::new(line_count - 1, last_len),
+                "\n",
+                None,
+            );
         }
+
+        let text = editor.with_buffer(|buffer| buffer.lines[line].text().to_string());
+        let mut insert = " ".repeat(column.saturating_sub(text.chars().count()));
+        insert.push_str(part);
+        end = editor.insert_at(
+            cosmic_text::Cursor::new(line, cursors::char_index(&text, column)),
+            &insert,
+            None,
+        );
     }
-    fn start_new_change(&self, editor: &mut SyntaxEditor<'static, 'static>, state: &mut State) {
-        self.finish_change(editor, state);
-        editor.start_change();
+    editor.set_cursor(end);
+}
+
+// moves the editor cursor, extra carets keep their own x instead of the editor's
+fn motion(editor: &mut Editor<'_>, cursor_x: Option<&mut Option<i32>>, motion: Motion) {
+    match cursor_x {
+        None => editor.action(cosmic_text::Action::Motion(motion)),
+        Some(cursor_x) => {
+            let cursor = editor.cursor();
+            if let Some((cursor, x)) =
+                editor.with_buffer_mut(|buffer| buffer.cursor_motion(cursor, *cursor_x, motion))
+            {
+                editor.set_cursor(cursor);
+                *cursor_x = x;
+            }
+        }
     }
 }
 
@@ -216,6 +558,12 @@ where
         }
 
         state.gutter_width.replace(gutter_width);
+        if state.char_width_size.get() != self.metrics.font_size {
+            state
+                .char_width
+                .replace(char_width(&mut font_system, self.metrics.font_size));
+            state.char_width_size.replace(self.metrics.font_size);
+        }
 
         // shaping takes 80% of the total drawing time, maybe behind redraw flag?
         // disabling syntax highlighting (using Editor instead) does *not* improve speed
@@ -231,11 +579,16 @@ where
                 )
             };
 
+            let convert_color = |color: syntect::highlighting::Color| {
+                cosmic_text::Color::rgba(color.r, color.g, color.b, color.a)
+            };
+            let canvas = Canvas {
+                w: image_w,
+                h: image_h,
+            };
+
             if self.line_number {
                 let (gutter, gutter_foreground) = {
-                    let convert_color = |color: syntect::highlighting::Color| {
-                        cosmic_text::Color::rgba(color.r, color.g, color.b, color.a)
-                    };
                     let syntax_theme = editor.theme();
                     let gutter = syntax_theme
                         .settings
@@ -333,32 +686,181 @@ where
 
             // FIXME: cosmic text highlight lines until end of buffer, not end of line
             let scroll_x = editor.with_buffer(|buffer| buffer.scroll().horizontal as i32);
+            // a block is drawn as a whole below, not as the selection of its head line
+            let selection = editor.selection();
+            if state.block.is_some() {
+                editor.set_selection(cosmic_text::Selection::None);
+            }
+            // vim's cursor shapes are drawn at the editor cursor after the text,
+            // the bar cosmic text draws there is left out
+            let cursor_color = editor.cursor_color();
+            let shape = self
+                .vim
+                .map(|vim| vim.read().expect("vim not readable").shape())
+                .filter(|shape| *shape != Shape::Bar);
+            let cursor_position = shape.and_then(|_| editor.cursor_position());
             editor.draw(&mut font_system, &mut swash_cache, |x, y, w, h, color| {
-                let mut w = w as i32;
-                let mut x = x;
-                // adjust drawing if x is behind gutter
-                if x < scroll_x {
-                    let hidden_w = scroll_x - x;
-                    if hidden_w >= w {
-                        return;
-                    }
-                    x = scroll_x;
-                    w = w - hidden_w;
+                if cursor_position == Some((x, y)) && w == 1 && color == cursor_color {
+                    return;
                 }
-                draw_rect(
+                draw_buffer_rect(pixels, &canvas, gutter_width, scroll_x, x, y, w, h, color);
+            });
+            if let (Some(shape), Some((x, y))) = (shape, cursor_position) {
+                let line_height = editor.with_buffer(|buffer| buffer.metrics().line_height) as u32;
+                let width = state.char_width.get().ceil() as u32;
+                let (y, h, color) = match shape {
+                    Shape::Underline => (y + line_height as i32 - 2, 2, cursor_color),
+                    _ => {
+                        let color = cursor_color;
+                        let color = cosmic_text::Color::rgba(color.r(), color.g(), color.b(), 0x80);
+                        (y, line_height, color)
+                    }
+                };
+                draw_buffer_rect(
                     pixels,
-                    Canvas {
-                        w: image_w as i32,
-                        h: image_h as i32,
-                    },
-                    Canvas { w: w, h: h as i32 },
-                    Offset {
-                        x: gutter_width + x - scroll_x,
-                        y,
-                    },
+                    &canvas,
+                    gutter_width,
+                    scroll_x,
+                    x,
+                    y,
+                    width,
+                    h,
                     color,
                 );
-            });
+            }
+            editor.set_selection(selection);
+
+            if !self.matches.is_empty() {
+                let match_color = {
+                    let syntax_theme = editor.theme();
+                    let color = syntax_theme
+                        .settings
+                        .find_highlight
+                        .map_or(editor.cursor_color(), convert_color);
+                    // blended on top of the text
+                    cosmic_text::Color::rgba(color.r(), color.g(), color.b(), 0x55)
+                };
+
+                editor.with_buffer(|buffer| {
+                    for run in buffer.layout_runs() {
+                        let first = self
+                            .matches
+                            .partition_point(|found| found.line < run.line_i);
+                        for found in self.matches[first..]
+                            .iter()
+                            .take_while(|found| found.line == run.line_i)
+                        {
+                            let x_start = index_x(&run, found.start);
+                            let x_end = index_x(&run, found.end);
+                            draw_buffer_rect(
+                                pixels,
+                                &canvas,
+                                gutter_width,
+                                scroll_x,
+                                x_start as i32,
+                                run.line_top as i32,
+                                (x_end - x_start).ceil() as u32,
+                                run.line_height as u32,
+                                match_color,
+                            );
+                        }
+                    }
+                });
+            }
+
+            let selection_color = {
+                let color = editor.selection_color();
+                // blended on top of the text
+                cosmic_text::Color::rgba(color.r(), color.g(), color.b(), 0x55)
+            };
+
+            if let Some(block) = state.block {
+                let (top, bottom) = block.lines();
+                let (left, right) = block.columns();
+                let char_width = state.char_width.get();
+                editor.with_buffer(|buffer| {
+                    let mut last_line = None;
+                    for run in buffer.layout_runs() {
+                        // the block covers short lines too, only the first run of wrapped lines
+                        if run.line_i < top || run.line_i > bottom || last_line == Some(run.line_i)
+                        {
+                            continue;
+                        }
+                        last_line = Some(run.line_i);
+                        draw_buffer_rect(
+                            pixels,
+                            &canvas,
+                            gutter_width,
+                            scroll_x,
+                            (left as f32 * char_width) as i32,
+                            run.line_top as i32,
+                            ((right - left) as f32 * char_width).ceil() as u32,
+                            run.line_height as u32,
+                            selection_color,
+                        );
+                    }
+                });
+            }
+
+            if !state.carets.is_empty() {
+                let cursor_color = editor.cursor_color();
+
+                editor.with_buffer(|buffer| {
+                    for run in buffer.layout_runs() {
+                        let run_start = run.glyphs.first().map_or(0, |glyph| glyph.start);
+                        let run_end = run.glyphs.last().map_or(0, |glyph| glyph.end);
+                        for caret in state.carets.iter() {
+                            let (start, end) = caret.bounds();
+                            if state.block.is_none()
+                                && start != end
+                                && start.line <= run.line_i
+                                && run.line_i <= end.line
+                            {
+                                let x_start = if start.line == run.line_i {
+                                    index_x(&run, start.index)
+                                } else {
+                                    0.0
+                                };
+                                // selections continue to the end of lines they span
+                                let x_end = if end.line == run.line_i {
+                                    index_x(&run, end.index)
+                                } else {
+                                    buffer.size().0.unwrap_or(run.line_w)
+                                };
+                                draw_buffer_rect(
+                                    pixels,
+                                    &canvas,
+                                    gutter_width,
+                                    scroll_x,
+                                    x_start as i32,
+                                    run.line_top as i32,
+                                    (x_end - x_start).ceil() as u32,
+                                    run.line_height as u32,
+                                    selection_color,
+                                );
+                            }
+
+                            let cursor = caret.cursor;
+                            if cursor.line == run.line_i
+                                && (run.glyphs.is_empty()
+                                    || (run_start <= cursor.index && cursor.index <= run_end))
+                            {
+                                draw_buffer_rect(
+                                    pixels,
+                                    &canvas,
+                                    gutter_width,
+                                    scroll_x,
+                                    index_x(&run, cursor.index) as i32,
+                                    run.line_top as i32,
+                                    1,
+                                    run.line_height as u32,
+                                    cursor_color,
+                                );
+                            }
+                        }
+                    }
+                });
+            }
 
             let handle = image::Handle::from_rgba(image_w as u32, image_h as u32, pixels_u8);
 
@@ -428,6 +930,7 @@ where
     ) -> iced::event::Status {
         let state = tree.state.downcast_mut::<State>();
         let gutter_width = state.gutter_width.get();
+        let mut edited = false;
 
         let mut font_system = font_system().write().expect("font system is not writable");
         let mut editor = self.editor.write().expect("editor is not writable");
@@ -438,143 +941,343 @@ where
         let mut status = Status::Ignored;
         match event {
             iced::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
-                state.modifiers_shift = modifiers.shift()
+                state.modifiers = modifiers
             }
             iced::Event::Keyboard(event) => {
+                // vim takes keys first, except those finishing a chord
+                let vim = match (&event, self.vim) {
+                    (
+                        keyboard::Event::KeyPressed {
+                            key,
+                            modifiers,
+                            text,
+                            ..
+                        },
+                        Some(vim),
+                    ) if state.focused && !keymap().is_pending() => {
+                        Input::from_key(key, *modifiers, text.as_deref()).and_then(|input| {
+                            vim.write().expect("vim not writable").key(
+                                input,
+                                &mut editor,
+                                clipboard,
+                            )
+                        })
+                    }
+                    _ => None,
+                };
+                let press = match &event {
+                    keyboard::Event::KeyPressed { key, modifiers, .. }
+                        if state.focused && vim.is_none() =>
+                    {
+                        let was_pending = keymap().is_pending();
+                        let press = keymap().press(KeyBind::new(*modifiers, key.clone()));
+                        if was_pending || keymap().is_pending() {
+                            shell.publish(Message::Chord(keymap().pending()));
+                        }
+                        Some(press)
+                    }
+                    _ => None,
+                };
                 if !state.focused {
                     // skip
-                } else if let Some(binding) = Binding::from_keyboard_event(event.clone()) {
+                } else if let Some(effects) = vim {
+                    edited = self.vim_effects(&mut editor, state, effects, shell);
+                    status = Status::Captured;
+                } else if let Some(Press::Bound(Action::Editor(binding))) = press {
                     // if binding exists, assume captured
+                    edited = binding.is_edit();
+                    let last_binding = state.last_binding.replace(binding);
+                    // the carets of a block stay, but it is no longer a block
+                    if !matches!(
+                        binding,
+                        Binding::SelectBlock(_) | Binding::Copy | Binding::Cut
+                    ) {
+                        state.block = None;
+                    }
                     match binding {
+                        Binding::Escape if state.mark => {
+                            state.mark = false;
+                            self.each_caret(&mut editor, state, |editor, _| {
+                                editor.set_selection(cosmic_text::Selection::None)
+                            });
+                        }
                         Binding::Escape => {
-                            shell.publish(Message::TabSearchClose);
+                            if state.carets.is_empty() {
+                                shell.publish(Message::TabSearchClose);
+                            } else {
+                                self.clear_carets(&mut editor, state);
+                            }
                         }
                         Binding::Enter => {
-                            self.start_new_change(&mut editor, state);
-                            editor.action(cosmic_text::Action::Enter)
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.action(cosmic_text::Action::Enter)
+                            });
+                            self.record(change, history::Kind::Other);
                         }
                         Binding::Unindent => {
-                            self.start_new_change(&mut editor, state);
-                            editor.action(cosmic_text::Action::Unindent)
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.action(cosmic_text::Action::Unindent)
+                            });
+                            self.record(change, history::Kind::Other);
                         }
                         Binding::Tab => {
-                            self.start_new_change(&mut editor, state);
-                            editor.insert_string("    ", None);
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.insert_string("    ", None)
+                            });
+                            self.record(change, history::Kind::Other);
                             // TODO
                             // if after first non-space character of line, use <tab>
                             // else, tab until equal tab width
                         }
                         Binding::Backspace => {
-                            // todo: start new change if previous is not a delete action
-                            editor.start_change();
-                            editor.action(cosmic_text::Action::Backspace);
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.action(cosmic_text::Action::Backspace)
+                            });
+                            self.record(change, history::Kind::Delete);
                         }
                         Binding::Delete => {
-                            // todo: start new change if previous is not a delete action
-                            editor.start_change();
-                            editor.action(cosmic_text::Action::Delete)
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.action(cosmic_text::Action::Delete)
+                            });
+                            self.record(change, history::Kind::Delete);
                         }
                         Binding::BackspaceWord => {
-                            self.start_new_change(&mut editor, state);
-                            if editor.delete_selection() {
-                                // selection deleted
-                            } else {
-                                let cursor_start = editor.cursor();
-                                editor.action(cosmic_text::Action::Motion(
-                                    cosmic_text::Motion::LeftWord,
-                                ));
-                                let cursor_end = editor.cursor();
-                                editor.delete_range(cursor_end, cursor_start);
-                                editor.set_cursor(cursor_end);
-                            }
+                            let change = self.each_caret(&mut editor, state, |editor, cursor_x| {
+                                if editor.delete_selection() {
+                                    // selection deleted
+                                } else {
+                                    let cursor_start = editor.cursor();
+                                    motion(editor, cursor_x, cosmic_text::Motion::LeftWord);
+                                    let cursor_end = editor.cursor();
+                                    editor.delete_range(cursor_end, cursor_start);
+                                    editor.set_cursor(cursor_end);
+                                }
+                            });
+                            self.record(change, history::Kind::Other);
                         }
                         Binding::DeleteWord => {
-                            self.start_new_change(&mut editor, state);
-                            if editor.delete_selection() {
-                                // selection deleted
-                            } else {
-                                let cursor_start = editor.cursor();
-                                editor.action(cosmic_text::Action::Motion(
-                                    cosmic_text::Motion::RightWord,
-                                ));
-                                let cursor_end = editor.cursor();
-                                editor.delete_range(cursor_start, cursor_end);
-                                editor.set_cursor(cursor_start);
-                            }
+                            let change = self.each_caret(&mut editor, state, |editor, cursor_x| {
+                                if editor.delete_selection() {
+                                    // selection deleted
+                                } else {
+                                    let cursor_start = editor.cursor();
+                                    motion(editor, cursor_x, cosmic_text::Motion::RightWord);
+                                    let cursor_end = editor.cursor();
+                                    editor.delete_range(cursor_start, cursor_end);
+                                    editor.set_cursor(cursor_start);
+                                }
+                            });
+                            self.record(change, history::Kind::Other);
+                        }
+                        Binding::Copy if state.block.is_some() => {
+                            let block = state.block.unwrap();
+                            let content = editor.with_buffer(|buffer| block.text(buffer));
+                            KILL_RING.lock().unwrap().kill(content.clone(), false);
+                            clipboard
+                                .write(iced::advanced::clipboard::Kind::Standard, content.clone());
+                            state.block_copy = Some(content);
                         }
                         Binding::Copy => {
-                            if let Some(selection) = editor.copy_selection() {
-                                clipboard
-                                    .write(iced::advanced::clipboard::Kind::Standard, selection);
+                            // one line per caret, first caret first
+                            let mut selections = Vec::new();
+                            let mark = state.mark;
+                            self.each_caret(&mut editor, state, |editor, _| {
+                                selections.extend(editor.copy_selection());
+                                // copying ends the region of a mark
+                                if mark {
+                                    editor.set_selection(cosmic_text::Selection::None);
+                                }
+                            });
+                            state.mark = false;
+                            if !selections.is_empty() {
+                                selections.reverse();
+                                let content =
+                                    KILL_RING.lock().unwrap().kill(selections.join("\n"), false);
+                                clipboard.write(iced::advanced::clipboard::Kind::Standard, content);
                             }
                         }
+                        Binding::Cut if state.block.is_some() => {
+                            let block = state.block.take().unwrap();
+                            let content = editor.with_buffer(|buffer| block.text(buffer));
+                            KILL_RING.lock().unwrap().kill(content.clone(), false);
+                            clipboard
+                                .write(iced::advanced::clipboard::Kind::Standard, content.clone());
+                            state.block_copy = Some(content);
+
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                editor.delete_selection();
+                            });
+                            self.record(change, history::Kind::Other);
+                        }
                         Binding::Cut => {
-                            self.start_new_change(&mut editor, state);
-                            if let Some(content) = editor.copy_selection() {
+                            let mut selections = Vec::new();
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                if let Some(content) = editor.copy_selection() {
+                                    selections.push(content);
+                                    editor.action(cosmic_text::Action::Delete);
+                                }
+                            });
+                            self.record(change, history::Kind::Other);
+                            if !selections.is_empty() {
+                                selections.reverse();
+                                let content =
+                                    KILL_RING.lock().unwrap().kill(selections.join("\n"), false);
+                                clipboard.write(iced::advanced::clipboard::Kind::Standard, content);
+                            }
+                        }
+                        Binding::KillLine => {
+                            let mut kills = Vec::new();
+                            let change = self.each_caret(&mut editor, state, |editor, _| {
+                                let cursor = editor.cursor();
+                                // the line break goes too when only blanks are left
+                                let end = editor.with_buffer(|buffer| {
+                                    let text = buffer.lines[cursor.line].text();
+                                    if text[cursor.index..].trim().is_empty()
+                                        && cursor.line + 1 < buffer.lines.len()
+                                    {
+                                        cosmic_text::Cursor::new(cursor.line + 1, 0)
+                                    } else {
+                                        cosmic_text::Cursor::new(cursor.line, text.len())
+                                    }
+                                });
+                                editor.set_selection(cosmic_text::Selection::Normal(end));
+                                kills.extend(editor.copy_selection());
+                                editor.delete_selection();
+                            });
+                            self.record(change, history::Kind::Other);
+                            if !kills.is_empty() {
+                                kills.reverse();
+                                let append = matches!(last_binding, Some(Binding::KillLine));
+                                let content =
+                                    KILL_RING.lock().unwrap().kill(kills.join("\n"), append);
                                 clipboard.write(iced::advanced::clipboard::Kind::Standard, content);
-                                editor.action(cosmic_text::Action::Delete);
                             }
                         }
+                        // yanks only at the editor cursor, yank-pop needs a single yank to replace
+                        Binding::Yank => {
+                            self.clear_carets(&mut editor, state);
+                            state.yanked = None;
+                            let content = KILL_RING
+                                .lock()
+                                .unwrap()
+                                .yank(clipboard.read(iced::advanced::clipboard::Kind::Standard));
+                            if let Some(content) = content {
+                                editor.start_change();
+                                editor.delete_selection();
+                                let start = editor.cursor();
+                                let end = editor.insert_at(start, &content, None);
+                                editor.set_cursor(end);
+                                self.record(editor.finish_change(), history::Kind::Other);
+                                state.yanked = Some((start, end));
+                            }
+                        }
+                        Binding::YankPop => {
+                            let yanked = state.yanked.take().filter(|_| {
+                                matches!(last_binding, Some(Binding::Yank | Binding::YankPop))
+                            });
+                            let content = yanked.and_then(|_| KILL_RING.lock().unwrap().pop());
+                            if let (Some((start, end)), Some(content)) = (yanked, content) {
+                                editor.start_change();
+                                editor.delete_range(start, end);
+                                let end = editor.insert_at(start, &content, None);
+                                editor.set_cursor(end);
+                                self.record(editor.finish_change(), history::Kind::Other);
+                                state.yanked = Some((start, end));
+                            }
+                        }
+                        Binding::SetMark => {
+                            state.mark = true;
+                            self.each_caret(&mut editor, state, |editor, _| {
+                                let cursor = editor.cursor();
+                                editor.set_selection(cosmic_text::Selection::Normal(cursor));
+                            });
+                        }
                         Binding::Paste => {
                             if let Some(content) =
                                 clipboard.read(iced::advanced::clipboard::Kind::Standard)
                             {
-                                self.start_new_change(&mut editor, state);
-                                editor.insert_string(&content, None);
+                                let change = if state.carets.is_empty()
+                                    && state.block_copy.as_ref() == Some(&content)
+                                {
+                                    editor.start_change();
+                                    paste_block(&mut editor, &content);
+                                    editor.finish_change()
+                                } else {
+                                    // a line per caret is spread over the carets, like it was copied
+                                    let mut lines: Vec<&str> = content.lines().collect();
+                                    let spread = !state.carets.is_empty()
+                                        && lines.len() == state.carets.len() + 1;
+                                    self.each_caret(&mut editor, state, |editor, _| {
+                                        // carets run last first
+                                        match lines.pop() {
+                                            Some(line) if spread => {
+                                                editor.insert_string(line, None)
+                                            }
+                                            _ => editor.insert_string(&content, None),
+                                        }
+                                    })
+                                };
+                                self.record(change, history::Kind::Other);
                             }
                         }
+                        // the region grows from the mark
+                        Binding::Move(binding_motion) if state.mark => {
+                            self.each_caret(&mut editor, state, |editor, cursor_x| {
+                                motion(editor, cursor_x, binding_motion.to_cosmic_motion())
+                            });
+                        }
                         Binding::Move(binding_motion) => {
-                            self.start_new_change(&mut editor, state);
-                            if let Some((start, end)) = editor.selection_bounds() {
-                                editor.set_selection(cosmic_text::Selection::None);
+                            self.each_caret(&mut editor, state, |editor, cursor_x| {
+                                if let Some((start, end)) = editor.selection_bounds() {
+                                    editor.set_selection(cosmic_text::Selection::None);
 
-                                match binding_motion {
-                                    // just move cursor
-                                    BindingMotion::Home
-                                    | BindingMotion::End
-                                    | BindingMotion::DocumentStart
-                                    | BindingMotion::DocumentEnd => {
-                                        editor.action(cosmic_text::Action::Motion(
+                                    match binding_motion {
+                                        // just move cursor
+                                        BindingMotion::Home
+                                        | BindingMotion::End
+                                        | BindingMotion::DocumentStart
+                                        | BindingMotion::DocumentEnd => motion(
+                                            editor,
+                                            cursor_x,
                                             binding_motion.to_cosmic_motion(),
-                                        ))
+                                        ),
+
+                                        // set cursor to start/end of selection
+                                        BindingMotion::Left
+                                        | BindingMotion::Up
+                                        | BindingMotion::WordLeft
+                                        | BindingMotion::PageUp => editor.set_cursor(start),
+
+                                        BindingMotion::Right
+                                        | BindingMotion::Down
+                                        | BindingMotion::PageDown
+                                        | BindingMotion::WordRight => editor.set_cursor(end),
                                     }
-
-                                    // set cursor to start/end of selection
-                                    BindingMotion::Left
-                                    | BindingMotion::Up
-                                    | BindingMotion::WordLeft
-                                    | BindingMotion::PageUp => editor.set_cursor(start),
-
-                                    BindingMotion::Right
-                                    | BindingMotion::Down
-                                    | BindingMotion::PageDown
-                                    | BindingMotion::WordRight => editor.set_cursor(end),
+                                } else {
+                                    motion(editor, cursor_x, binding_motion.to_cosmic_motion())
                                 }
-                            } else {
-                                editor.action(cosmic_text::Action::Motion(
-                                    binding_motion.to_cosmic_motion(),
-                                ))
-                            }
+                            });
                         }
                         Binding::Select(binding_motion) => {
-                            let cursor = editor.cursor();
+                            self.each_caret(&mut editor, state, |editor, cursor_x| {
+                                let cursor = editor.cursor();
 
-                            if editor.selection_bounds().is_none() {
-                                editor.set_selection(cosmic_text::Selection::Normal(cursor));
-                            }
+                                if editor.selection_bounds().is_none() {
+                                    editor.set_selection(cosmic_text::Selection::Normal(cursor));
+                                }
 
-                            editor.action(cosmic_text::Action::Motion(
-                                binding_motion.to_cosmic_motion(),
-                            ));
+                                motion(editor, cursor_x, binding_motion.to_cosmic_motion());
 
-                            // deselect if go back to same position
-                            if let Some((start, end)) = editor.selection_bounds() {
-                                if start.line == end.line && start.index == end.index {
-                                    editor.set_selection(cosmic_text::Selection::None);
+                                // deselect if go back to same position
+                                if let Some((start, end)) = editor.selection_bounds() {
+                                    if start.line == end.line && start.index == end.index {
+                                        editor.set_selection(cosmic_text::Selection::None);
+                                    }
                                 }
-                            }
+                            });
                         }
                         Binding::SelectAll => {
+                            self.clear_carets(&mut editor, state);
                             let has_content = editor.with_buffer(|buffer| {
                                 // buffer has content
                                 buffer.lines.len() > 1
@@ -599,31 +1302,42 @@ where
                                 ));
                             }
                         }
-                        Binding::Undo => {
-                            if let Some(change) = &mut editor.finish_change() {
-                                change.reverse();
-                                editor.apply_change(&change);
-                                state.redo_buffer.push(change.clone());
-                            } else {
-                                if let Some(change) = &mut state.undo_buffer.pop() {
-                                    change.reverse();
-                                    editor.apply_change(&change);
-                                    state.redo_buffer.push(change.clone());
-                                }
+                        Binding::SelectBlock(binding_motion) => {
+                            let mut block = state.block.unwrap_or_else(|| {
+                                let cursor = editor.cursor();
+                                let column = editor.with_buffer(|buffer| {
+                                    cursors::column(buffer.lines[cursor.line].text(), cursor.index)
+                                });
+                                cursors::Block::new((cursor.line, column))
+                            });
+                            let last_line = editor.with_buffer(|buffer| buffer.lines.len() - 1);
+                            let (line, column) = &mut block.head;
+                            match binding_motion {
+                                BindingMotion::Up => *line = line.saturating_sub(1),
+                                BindingMotion::Down => *line = (*line + 1).min(last_line),
+                                BindingMotion::Left => *column = column.saturating_sub(1),
+                                BindingMotion::Right => *column += 1,
+                                _ => {}
                             }
+                            self.set_block(&mut editor, state, block);
                         }
-                        Binding::Redo => {
-                            if let Some(_) = &mut editor.finish_change() {
-                                // no redos allowed if changing
-                            } else {
-                                if let Some(change) = &mut state.redo_buffer.pop() {
-                                    change.reverse();
-                                    editor.apply_change(&change);
-                                }
-                            }
+                        Binding::AddNextOccurrence => {
+                            self.add_next_occurrence(&mut editor, state);
                         }
+                        Binding::SelectAllOccurrences => {
+                            self.select_all_occurrences(&mut editor, state);
+                        }
+                        Binding::Undo => self.undo(&mut editor, state),
+                        Binding::Redo => self.redo(&mut editor, state),
                     }
                     status = Status::Captured;
+                } else if let Some(Press::Bound(Action::App(message))) = press {
+                    state.last_binding = None;
+                    shell.publish(message);
+                    status = Status::Captured;
+                } else if let Some(Press::Pending | Press::Aborted) = press {
+                    // the keys of a chord are not typed
+                    status = Status::Captured;
                 } else if let keyboard::Event::KeyPressed {
                     text, modifiers, ..
                 } = event
@@ -631,8 +1345,13 @@ where
                     if !modifiers.logo() && !modifiers.control() && !modifiers.alt() {
                         if let Some(text) = text {
                             if let Some(c) = text.chars().find(|c| !c.is_control()) {
-                                editor.start_change();
-                                editor.insert_string(&c.to_string(), None);
+                                state.block = None;
+                                state.last_binding = None;
+                                let change = self.each_caret(&mut editor, state, |editor, _| {
+                                    editor.insert_string(&c.to_string(), None)
+                                });
+                                self.record(change, history::Kind::Insert);
+                                edited = true;
                                 status = Status::Captured
                             }
                         }
@@ -641,7 +1360,8 @@ where
             }
             iced::Event::Mouse(event) => match event {
                 iced::mouse::Event::ButtonPressed(iced::mouse::Button::Left) => {
-                    self.start_new_change(&mut editor, state);
+                    state.mark = false;
+                    state.last_binding = None;
                     if let Some(pos) = cursor.position_in(layout.bounds()) {
                         let mut x = pos.x - self.padding.left - gutter_width as f32;
                         let y = pos.y - self.padding.top;
@@ -669,6 +1389,19 @@ where
                                 ClickKind::Single
                             };
 
+                            // alt click keeps the current cursor as an extra caret
+                            // and starts a block selection when dragged
+                            if state.modifiers.alt() && matches!(kind, ClickKind::Single) {
+                                state
+                                    .carets
+                                    .push(Caret::new(editor.cursor(), editor.selection()));
+                                editor.set_selection(cosmic_text::Selection::None);
+                                state.block = None;
+                                state.block_start = Some(self.block_position(&editor, state, x, y));
+                            } else {
+                                self.clear_carets(&mut editor, state);
+                            }
+
                             match kind {
                                 ClickKind::Single => editor.action(cosmic_text::Action::Click {
                                     x: x as i32,
@@ -687,9 +1420,20 @@ where
                                     })
                                 }
                             }
+                            if !state.carets.is_empty() {
+                                let cursor = cursors::position(&editor.cursor());
+                                cursors::dedup(&mut state.carets);
+                                state
+                                    .carets
+                                    .retain(|caret| cursors::position(&caret.cursor) != cursor);
+                                editor.set_redraw(true);
+                            }
                             state.click_last = Some((kind, Instant::now(), (x, y)));
                             state.dragging = true;
                         }
+                        if !state.focused {
+                            shell.publish(Message::EditorFocused);
+                        }
                         state.focused = true;
                         status = Status::Captured;
                     } else {
@@ -699,6 +1443,7 @@ where
                 }
                 iced::mouse::Event::ButtonReleased(iced::mouse::Button::Left) => {
                     state.dragging = false;
+                    state.block_start = None;
                     self.auto_scroll = None;
 
                     status = Status::Captured;
@@ -714,10 +1459,21 @@ where
 
                             x += buffer_scroll.horizontal;
 
-                            editor.action(cosmic_text::Action::Drag {
-                                x: x as i32,
-                                y: y as i32,
-                            });
+                            if let Some(anchor) = state.block_start {
+                                let head = self.block_position(&editor, state, x, y);
+                                if head != anchor || state.block.is_some() {
+                                    self.set_block(
+                                        &mut editor,
+                                        state,
+                                        cursors::Block { anchor, head },
+                                    );
+                                }
+                            } else {
+                                editor.action(cosmic_text::Action::Drag {
+                                    x: x as i32,
+                                    y: y as i32,
+                                });
+                            }
                             let auto_scroll = editor.with_buffer(|buffer| {
                                 //TODO: ideal auto scroll speed
                                 let speed = 1.01;
@@ -760,7 +1516,7 @@ where
                         lines_y = lines_y.trunc();
 
                         // Note: mouse event + modifiers is still in PR https://github.com/iced-rs/iced/pull/2733
-                        if state.modifiers_shift {
+                        if state.modifiers.shift() {
                             // scroll only y
                             // Note: skipping set_scroll/action makes it a tad faster
                             if lines_y != 0.0 {
@@ -809,6 +1565,12 @@ where
             _ => {}
         };
 
+        if edited {
+            // the region of a mark ends with an edit
+            state.mark = false;
+            shell.publish(Message::TabEdited);
+        }
+
         status
     }
 
@@ -868,8 +1630,6 @@ struct State {
     dragging: bool,
     // last click
     click_last: Option<(ClickKind, time::Instant, (f32, f32))>,
-    undo_buffer: Vec<cosmic_text::Change>,
-    redo_buffer: Vec<cosmic_text::Change>,
     // gutter_width set on first draw
     // is a Cell because written in draw
     gutter_width: Cell<i32>,
@@ -878,7 +1638,26 @@ struct State {
     parial_scroll: f32,
     focused: bool,
 
-    modifiers_shift: bool, // solely for shift scroll
+    // shift scroll and alt click
+    modifiers: keyboard::Modifiers,
+    // cursors besides the editor's, sorted by position
+    carets: Vec<Caret>,
+    // block selection the carets were made from
+    block: Option<cursors::Block>,
+    // where an alt drag started, it becomes a block once the mouse moves
+    block_start: Option<(usize, usize)>,
+    // last block copied, pasting it again keeps the block shape
+    block_copy: Option<String>,
+    // monospace char width for block columns, measured on draw when the font size changes
+    char_width: Cell<f32>,
+    // font size char_width was measured at
+    char_width_size: Cell<f32>,
+    // a mark is set, motions grow the selection from it
+    mark: bool,
+    // binding of the last key, kills in a row add up and only a yank is popped
+    last_binding: Option<Binding>,
+    // text of the last yank, replaced by a yank-pop
+    yanked: Option<(cosmic_text::Cursor, cosmic_text::Cursor)>,
 }
 
 impl State {
@@ -886,11 +1665,18 @@ impl State {
         Self {
             dragging: false,
             click_last: None,
-            undo_buffer: Vec::new(),
-            redo_buffer: Vec::new(),
             gutter_width: Cell::new(0),
             render_handle: RefCell::new(None),
-            modifiers_shift: false,
+            modifiers: keyboard::Modifiers::default(),
+            carets: Vec::new(),
+            block: None,
+            block_start: None,
+            block_copy: None,
+            char_width: Cell::new(0.0),
+            char_width_size: Cell::new(0.0),
+            mark: false,
+            last_binding: None,
+            yanked: None,
             max_line_width: Cell::new(0.0),
             parial_scroll: 0.0,
             focused: false,
@@ -912,7 +1698,8 @@ impl operation::Focusable for State {
     }
 }
 
-// event -> binding -> editor.action
+// event -> keymap -> binding -> editor.action
+#[derive(Debug, Clone, Copy)]
 pub enum Binding {
     Escape,
     Enter,
@@ -926,12 +1713,27 @@ pub enum Binding {
     Cut,
     Paste,
     SelectAll,
+    // adds a caret at the next occurrence of the selection
+    AddNextOccurrence,
+    // adds a caret at every occurrence of the selection
+    SelectAllOccurrences,
+    // grows the block selection
+    SelectBlock(BindingMotion),
     Move(BindingMotion),
     Select(BindingMotion),
     Undo,
     Redo,
+    // cuts to the line end, or the line break at the end, into the kill ring
+    KillLine,
+    // pastes the last kill
+    Yank,
+    // replaces the text just yanked with the kill before it
+    YankPop,
+    // motions select from here until escape or an edit
+    SetMark,
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum BindingMotion {
     Left,
     Right,
@@ -948,20 +1750,6 @@ pub enum BindingMotion {
 }
 
 impl BindingMotion {
-    fn from_named_key(key: keyboard::key::Named) -> Option<Self> {
-        match key {
-            keyboard::key::Named::ArrowLeft => Some(Self::Left),
-            keyboard::key::Named::ArrowRight => Some(Self::Right),
-            keyboard::key::Named::ArrowDown => Some(Self::Down),
-            keyboard::key::Named::ArrowUp => Some(Self::Up),
-            keyboard::key::Named::PageUp => Some(Self::PageUp),
-            keyboard::key::Named::PageDown => Some(Self::PageDown),
-            keyboard::key::Named::Home => Some(Self::Home),
-            keyboard::key::Named::End => Some(Self::End),
-            _ => None,
-        }
-    }
-
     fn to_cosmic_motion(self) -> Motion {
         match self {
             BindingMotion::Left => Motion::Left,
@@ -981,72 +1769,100 @@ impl BindingMotion {
 }
 
 impl Binding {
-    fn from_keyboard_event(event: iced::keyboard::Event) -> Option<Self> {
-        match event {
-            keyboard::Event::KeyPressed { key, modifiers, .. } => match key.as_ref() {
-                keyboard::Key::Named(keyboard::key::Named::Escape) => Some(Self::Escape),
-                keyboard::Key::Named(keyboard::key::Named::Enter) => Some(Self::Enter),
-                keyboard::Key::Named(keyboard::key::Named::Tab) if modifiers.shift() => {
-                    Some(Self::Unindent)
-                }
-                keyboard::Key::Named(keyboard::key::Named::Tab) => Some(Self::Tab),
-                keyboard::Key::Named(keyboard::key::Named::Backspace) => {
-                    if modifiers.command() {
-                        Some(Self::BackspaceWord)
-                    } else {
-                        Some(Self::Backspace)
-                    }
-                }
-                keyboard::Key::Named(keyboard::key::Named::Delete) => {
-                    if modifiers.command() {
-                        Some(Self::DeleteWord)
-                    } else {
-                        Some(Self::Delete)
-                    }
-                }
-                keyboard::Key::Character("c") if modifiers.command() => Some(Self::Copy),
-                keyboard::Key::Character("x") if modifiers.command() => Some(Self::Cut),
-                keyboard::Key::Character("v") if modifiers.command() => Some(Self::Paste),
-                keyboard::Key::Character("a") if modifiers.command() => Some(Self::SelectAll),
-                keyboard::Key::Character("z") if modifiers.command() && modifiers.shift() => {
-                    Some(Self::Redo)
-                }
-                keyboard::Key::Character("z") if modifiers.command() => Some(Self::Undo),
-                keyboard::Key::Named(name) => {
-                    let motion = BindingMotion::from_named_key(name)?;
-                    let motion = if modifiers.macos_command() {
-                        match motion {
-                            BindingMotion::Left => BindingMotion::Home,
-                            BindingMotion::Right => BindingMotion::End,
-                            _ => motion,
-                        }
-                    } else {
-                        motion
-                    };
-
-                    let motion = if modifiers.jump() {
-                        match motion {
-                            BindingMotion::Left => BindingMotion::WordLeft,
-                            BindingMotion::Right => BindingMotion::WordRight,
-                            BindingMotion::Home => BindingMotion::DocumentStart,
-                            BindingMotion::End => BindingMotion::DocumentEnd,
-                            _ => motion,
-                        }
-                    } else {
-                        motion
-                    };
+    // bindings that may change the buffer content
+    fn is_edit(&self) -> bool {
+        match self {
+            Binding::Enter
+            | Binding::Tab
+            | Binding::Unindent
+            | Binding::Backspace
+            | Binding::BackspaceWord
+            | Binding::Delete
+            | Binding::DeleteWord
+            | Binding::Cut
+            | Binding::Paste
+            | Binding::Undo
+            | Binding::Redo
+            | Binding::KillLine
+            | Binding::Yank
+            | Binding::YankPop => true,
+            Binding::Escape
+            | Binding::Copy
+            | Binding::SelectAll
+            | Binding::AddNextOccurrence
+            | Binding::SelectAllOccurrences
+            | Binding::SelectBlock(_)
+            | Binding::Move(_)
+            | Binding::Select(_)
+            | Binding::SetMark => false,
+        }
+    }
+}
 
-                    Some(if modifiers.shift() {
-                        Self::Select(motion)
-                    } else {
-                        Self::Move(motion)
-                    })
-                }
-                _ => None,
-            },
-            _ => None,
+// width of a monospace char at font_size
+fn char_width(font_system: &mut cosmic_text::FontSystem, font_size: f32) -> f32 {
+    let attrs = Attrs::new().family(cosmic_text::Family::Monospace);
+    let mut buffer_line = BufferLine::new(
+        "0",
+        LineEnding::default(),
+        AttrsList::new(&attrs),
+        cosmic_text::Shaping::Advanced,
+    );
+    let layout = buffer_line.layout(font_system, 1.0, None, cosmic_text::Wrap::None, None, 8);
+    layout[0].w * font_size
+}
+
+// x position of a byte index inside a layout run, assumes left-to-right text
+fn index_x(run: &cosmic_text::LayoutRun, index: usize) -> f32 {
+    for glyph in run.glyphs.iter() {
+        if index <= glyph.start {
+            return glyph.x;
+        }
+        if index < glyph.end {
+            let ratio = (index - glyph.start) as f32 / (glyph.end - glyph.start) as f32;
+            return glyph.x + glyph.w * ratio;
+        }
+    }
+    run.glyphs.last().map_or(0.0, |glyph| glyph.x + glyph.w)
+}
+
+// draws a rect given in buffer coordinates, clipping what is scrolled behind the gutter
+#[allow(clippy::too_many_arguments)]
+fn draw_buffer_rect(
+    pixels: &mut [u32],
+    canvas: &Canvas,
+    gutter_width: i32,
+    scroll_x: i32,
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+    color: cosmic_text::Color,
+) {
+    let mut w = w as i32;
+    let mut x = x;
+    // adjust drawing if x is behind gutter
+    if x < scroll_x {
+        let hidden_w = scroll_x - x;
+        if hidden_w >= w {
+            return;
         }
+        x = scroll_x;
+        w -= hidden_w;
     }
+    draw_rect(
+        pixels,
+        Canvas {
+            w: canvas.w,
+            h: canvas.h,
+        },
+        Canvas { w, h: h as i32 },
+        Offset {
+            x: gutter_width + x - scroll_x,
+            y,
+        },
+        color,
+    );
 }
 
 struct Canvas {
@@ -0,0 +1,2496 @@
+use std::{
+    collections::HashMap,
+    fmt, mem,
+    sync::{LazyLock, Mutex},
+};
+
+use cosmic_text::{Buffer, Change, Cursor, Edit, Selection};
+use iced::{
+    advanced::{Clipboard, clipboard::Kind::Standard},
+    keyboard::{Key, Modifiers, key::Named},
+};
+use regex::RegexBuilder;
+
+use super::{
+    cursors::{self, Block, Caret, position},
+    text_box::{Editor, paste_block},
+};
+use crate::Message;
+
+// an indent level, like the text box's tab
+const INDENT: &str = "    ";
+// counts are cut to this, a long run of digits must not hang the editor
+const MAX_COUNT: usize = 9_999;
+
+// registers are shared by all editors, like in vim
+static REGISTERS: LazyLock<Mutex<HashMap<char, Register>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    // typing an ex command after :
+    Command,
+}
+
+// how the text box draws the cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Bar,
+    Block,
+    // an operator waits for its motion
+    Underline,
+}
+
+// a key as vim sees it, shift is part of the char
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Char(char),
+    Ctrl(char),
+    Escape,
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+impl Input {
+    // none for keys vim leaves to the keymap, like alt and super chords
+    pub fn from_key(key: &Key, modifiers: Modifiers, text: Option<&str>) -> Option<Self> {
+        if modifiers.alt() || modifiers.logo() {
+            return None;
+        }
+        if modifiers.control() {
+            return match key.as_ref() {
+                Key::Character("[") => Some(Self::Escape),
+                Key::Character(c) => c.chars().next().map(Self::Ctrl),
+                _ => None,
+            };
+        }
+        match key.as_ref() {
+            Key::Named(Named::Escape) => Some(Self::Escape),
+            Key::Named(Named::Enter) => Some(Self::Enter),
+            Key::Named(Named::Backspace) => Some(Self::Backspace),
+            Key::Named(Named::Delete) => Some(Self::Delete),
+            Key::Named(Named::Tab) => Some(Self::Tab),
+            Key::Named(Named::ArrowLeft) => Some(Self::Left),
+            Key::Named(Named::ArrowRight) => Some(Self::Right),
+            Key::Named(Named::ArrowUp) => Some(Self::Up),
+            Key::Named(Named::ArrowDown) => Some(Self::Down),
+            Key::Named(Named::Home) => Some(Self::Home),
+            Key::Named(Named::End) => Some(Self::End),
+            Key::Named(Named::Space) => Some(Self::Char(' ')),
+            _ => text?.chars().find(|c| !c.is_control()).map(Self::Char),
+        }
+    }
+}
+
+// as shown while a command is typed
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Char(c) => write!(f, "{}", c),
+            Input::Ctrl(c) => write!(f, "^{}", c.to_ascii_uppercase()),
+            Input::Escape => write!(f, "<Esc>"),
+            Input::Enter => write!(f, "<CR>"),
+            Input::Backspace => write!(f, "<BS>"),
+            Input::Delete => write!(f, "<Del>"),
+            Input::Tab => write!(f, "<Tab>"),
+            Input::Left => write!(f, "<Left>"),
+            Input::Right => write!(f, "<Right>"),
+            Input::Up => write!(f, "<Up>"),
+            Input::Down => write!(f, "<Down>"),
+            Input::Home => write!(f, "<Home>"),
+            Input::End => write!(f, "<End>"),
+        }
+    }
+}
+
+// what the text box does after vim took a key
+pub(crate) enum Effect {
+    // edits made by the key, one undo step
+    Edit(Change),
+    Undo,
+    Redo,
+    // the selection of visual block mode, none outside of it
+    Block(Option<Block>),
+    // carets typed at besides the cursor, like after I in visual block mode
+    Carets(Vec<Caret>),
+    Publish(Message),
+}
+
+// modal editing in front of the keymap. normal and visual modes take plain keys,
+// insert mode leaves them to the keymap and the text box
+pub struct Vim {
+    mode: Mode,
+    // keys of the command being typed, like "a2d
+    keys: Vec<Input>,
+    // the ex command being typed after :
+    command: String,
+    // lines of the visual selection : was typed in, for '<,'>
+    visual_lines: Option<(usize, usize)>,
+    // where the visual selection started
+    anchor: Cursor,
+    // char column j and k keep to, usize::MAX after $
+    column: Option<usize>,
+    last_find: Option<Find>,
+    // keys of the last change, . types them again
+    last_change: Vec<Input>,
+    // keys of the change being typed, until back in normal mode
+    change: Option<Vec<Input>>,
+    // keys typed since insert mode started, typed again for a count like 3i
+    inserted: Vec<Input>,
+    insert_count: usize,
+    // shown until the next key, like the errors of ex commands
+    message: Option<String>,
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Normal,
+            keys: Vec::new(),
+            command: String::new(),
+            visual_lines: None,
+            anchor: Cursor::new(0, 0),
+            column: None,
+            last_find: None,
+            last_change: Vec::new(),
+            change: None,
+            inserted: Vec::new(),
+            insert_count: 1,
+            message: None,
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        let operator = |key: &Input| matches!(key, Input::Char('d' | 'c' | 'y' | '<' | '>'));
+        match self.mode {
+            Mode::Insert => Shape::Bar,
+            Mode::Normal if self.keys.iter().any(operator) => Shape::Underline,
+            _ => Shape::Block,
+        }
+    }
+
+    // the mode or the ex command line, and the keys typed so far
+    pub fn status(&self) -> (String, String) {
+        let mode = match self.mode {
+            Mode::Command => format!(":{}", self.command),
+            _ if self.message.is_some() => self.message.clone().unwrap_or_default(),
+            Mode::Normal => String::new(),
+            Mode::Insert => "-- INSERT --".to_string(),
+            Mode::Visual => "-- VISUAL --".to_string(),
+            Mode::VisualLine => "-- VISUAL LINE --".to_string(),
+            Mode::VisualBlock => "-- VISUAL BLOCK --".to_string(),
+        };
+        let keys = self.keys.iter().map(Input::to_string).collect();
+        (mode, keys)
+    }
+
+    // none if the key is not for vim and goes on to the keymap
+    pub(crate) fn key(
+        &mut self,
+        input: Input,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+    ) -> Option<Vec<Effect>> {
+        let mut effects = Vec::new();
+        editor.start_change();
+        let taken = self.input(input, editor, clipboard, &mut effects, false);
+        let change = editor.finish_change();
+        if !taken {
+            return None;
+        }
+        if let Some(change) = change {
+            effects.insert(0, Effect::Edit(change));
+        }
+        self.show(editor, &mut effects);
+        editor.set_redraw(true);
+        Some(effects)
+    }
+
+    // replayed keys of . are typed by vim itself, also in insert mode
+    fn input(
+        &mut self,
+        input: Input,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+        effects: &mut Vec<Effect>,
+        replay: bool,
+    ) -> bool {
+        if !replay {
+            self.message = None;
+        }
+        match self.mode {
+            Mode::Insert => self.insert_input(input, editor, effects, replay),
+            Mode::Command => {
+                self.command_input(input, editor, effects);
+                true
+            }
+            _ => self.normal_input(input, editor, clipboard, effects, replay),
+        }
+    }
+
+    fn insert_input(
+        &mut self,
+        input: Input,
+        editor: &mut Editor<'_>,
+        effects: &mut Vec<Effect>,
+        replay: bool,
+    ) -> bool {
+        if let Some(change) = &mut self.change {
+            change.push(input);
+        }
+        if input == Input::Escape {
+            self.leave_insert(editor, effects);
+            return true;
+        }
+        self.inserted.push(input);
+        if replay {
+            type_input(editor, input);
+        }
+        replay
+    }
+
+    fn enter_insert(&mut self, count: usize) {
+        self.mode = Mode::Insert;
+        self.inserted.clear();
+        self.insert_count = count;
+        self.column = None;
+    }
+
+    fn leave_insert(&mut self, editor: &mut Editor<'_>, effects: &mut Vec<Effect>) {
+        let inserted = mem::take(&mut self.inserted);
+        for _ in 1..self.insert_count {
+            for input in &inserted {
+                type_input(editor, *input);
+            }
+        }
+        self.insert_count = 1;
+        if let Some(change) = self.change.take() {
+            self.last_change = change;
+        }
+        self.mode = Mode::Normal;
+        effects.push(Effect::Carets(Vec::new()));
+
+        // the cursor steps back onto the last char typed
+        let cursor = editor.cursor();
+        if cursor.index > 0 {
+            let index =
+                editor.with_buffer(|buffer| prev_char(line(buffer, cursor.line), cursor.index));
+            editor.set_cursor(Cursor::new(cursor.line, index));
+        }
+    }
+
+    fn command_input(&mut self, input: Input, editor: &mut Editor<'_>, effects: &mut Vec<Effect>) {
+        match input {
+            Input::Escape => self.mode = Mode::Normal,
+            Input::Enter => {
+                let command = mem::take(&mut self.command);
+                self.mode = Mode::Normal;
+                self.ex(&command, editor, effects);
+                self.visual_lines = None;
+            }
+            // backspace on an empty command line leaves it
+            Input::Backspace if self.command.pop().is_none() => self.mode = Mode::Normal,
+            Input::Char(c) => self.command.push(c),
+            _ => {}
+        }
+    }
+
+    fn normal_input(
+        &mut self,
+        input: Input,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+        effects: &mut Vec<Effect>,
+        replay: bool,
+    ) -> bool {
+        if self.keys.is_empty() {
+            match input {
+                // clears carets or closes the search
+                Input::Escape if self.mode == Mode::Normal => return false,
+                // shortcuts like ctrl+s stay with the keymap
+                Input::Ctrl(c) if !matches!(c, 'r' | 'v' | 'd' | 'u') => return false,
+                _ => {}
+            }
+        } else if input == Input::Escape {
+            self.keys.clear();
+            return true;
+        }
+
+        // a selection made with the mouse is taken as a visual selection
+        if self.mode == Mode::Normal
+            && let Selection::Normal(anchor) = editor.selection()
+            && anchor != editor.cursor()
+        {
+            self.mode = Mode::Visual;
+            self.anchor = anchor;
+        }
+
+        self.keys.push(input);
+        let visual = self.mode != Mode::Normal;
+        let command = match parse(&self.keys, visual) {
+            Ok(command) => command,
+            Err(Parse::Incomplete) => return true,
+            Err(Parse::Invalid) => {
+                self.keys.clear();
+                return true;
+            }
+        };
+
+        let keys = mem::take(&mut self.keys);
+        let changes = !replay && !visual && command.changes();
+        if changes {
+            self.change = Some(keys);
+        }
+        self.run(command, editor, clipboard, effects);
+        if changes && self.mode != Mode::Insert {
+            self.last_change = self.change.take().unwrap_or_default();
+        }
+
+        // outside of insert mode the cursor stays on a char
+        if self.mode != Mode::Insert {
+            let cursor = editor.cursor();
+            let clamped = editor.with_buffer(|buffer| on_char(buffer, cursor));
+            if clamped != cursor {
+                editor.set_cursor(clamped);
+            }
+        }
+        true
+    }
+
+    // the selection as vim shows it, the char under the cursor is selected too
+    fn show(&self, editor: &mut Editor<'_>, effects: &mut Vec<Effect>) {
+        let cursor = editor.cursor();
+        let selection = match self.mode {
+            Mode::Visual if position(&cursor) < position(&self.anchor) => {
+                let anchor = editor.with_buffer(|buffer| after(buffer, self.anchor));
+                Selection::Normal(anchor)
+            }
+            Mode::Visual => Selection::Normal(self.anchor),
+            Mode::VisualLine => Selection::Line(self.anchor),
+            _ => Selection::None,
+        };
+        editor.set_selection(selection);
+        let block = editor.with_buffer(|buffer| self.block(buffer, cursor));
+        effects.push(Effect::Block(block));
+    }
+
+    // the visual block from the anchor to the cursor, both columns included
+    fn block(&self, buffer: &Buffer, cursor: Cursor) -> Option<Block> {
+        if self.mode != Mode::VisualBlock {
+            return None;
+        }
+        let column = |at: Cursor| cursors::column(line(buffer, at.line), at.index);
+        let (anchor, head) = (column(self.anchor), column(cursor));
+        let (anchor, head) = if head >= anchor {
+            (anchor, head + 1)
+        } else {
+            (anchor + 1, head)
+        };
+        Some(Block {
+            anchor: (self.anchor.line, anchor),
+            head: (cursor.line, head),
+        })
+    }
+
+    fn run(
+        &mut self,
+        command: Command,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+        effects: &mut Vec<Effect>,
+    ) {
+        let Command {
+            register,
+            count,
+            act,
+        } = command;
+        let n = count.unwrap_or(1);
+        let cursor = editor.cursor();
+        let visual = self.mode != Mode::Normal;
+
+        match act {
+            Act::Move(motion) => {
+                let target =
+                    editor.with_buffer(|buffer| self.motion(buffer, cursor, motion, count));
+                if let Some((target, _)) = target {
+                    editor.set_cursor(target);
+                }
+            }
+            Act::Operate {
+                operator,
+                target,
+                count: inner,
+            } => {
+                let count = match (count, inner) {
+                    (None, None) => None,
+                    (count, inner) => Some(
+                        count
+                            .unwrap_or(1)
+                            .saturating_mul(inner.unwrap_or(1))
+                            .min(MAX_COUNT),
+                    ),
+                };
+                let range = editor.with_buffer(|buffer| {
+                    self.target_range(buffer, cursor, operator, target, count)
+                });
+                if let Some(range) = range {
+                    self.operate(operator, range, register, editor, clipboard, effects);
+                }
+            }
+            Act::OperateSelection(operator, lines) => {
+                if self.mode == Mode::VisualBlock && !lines {
+                    self.operate_block(operator, register, editor, clipboard, effects);
+                } else {
+                    let range = editor.with_buffer(|buffer| self.selection(buffer, cursor, lines));
+                    self.mode = Mode::Normal;
+                    self.operate(operator, range, register, editor, clipboard, effects);
+                }
+            }
+            Act::SelectObject(object) => {
+                if let Some((start, end)) = editor.with_buffer(|buffer| object.find(buffer, cursor))
+                    && position(&start) < position(&end)
+                {
+                    let last = editor.with_buffer(|buffer| prev(buffer, end).unwrap_or(end));
+                    self.anchor = start;
+                    editor.set_cursor(last);
+                    self.mode = Mode::Visual;
+                }
+            }
+            Act::Put { .. } if visual => self.put_selection(register, editor, clipboard),
+            Act::Put { before } => {
+                match load(register, clipboard) {
+                    Some(content) => self.put(&content, before, n, editor),
+                    None => self.nothing_in(register),
+                };
+            }
+            Act::Undo => effects.extend((0..n).map(|_| Effect::Undo)),
+            Act::Redo => effects.extend((0..n).map(|_| Effect::Redo)),
+            Act::Insert(entry) => self.insert(entry, n, editor, effects),
+            Act::Visual(mode) => {
+                if self.mode == mode {
+                    self.mode = Mode::Normal;
+                } else {
+                    if self.mode == Mode::Normal {
+                        self.anchor = cursor;
+                    }
+                    self.mode = mode;
+                }
+            }
+            Act::SwapAnchor => {
+                editor.set_cursor(self.anchor);
+                self.anchor = cursor;
+            }
+            Act::ExitVisual => self.mode = Mode::Normal,
+            Act::Replace(c) => {
+                let text = editor.with_buffer(|buffer| line(buffer, cursor.line).to_string());
+                if text[cursor.index..].chars().count() >= n {
+                    let end = chars_after(&text, cursor.index, n);
+                    editor.delete_range(cursor, Cursor::new(cursor.line, end));
+                    let end = editor.insert_at(cursor, &c.to_string().repeat(n), None);
+                    editor.set_cursor(Cursor::new(end.line, prev_char(&text, end.index)));
+                }
+            }
+            Act::Join if visual => {
+                let (start, end) = ordered(self.anchor, cursor);
+                self.mode = Mode::Normal;
+                join(editor, start.line, (end.line - start.line).max(1));
+            }
+            Act::Join => join(editor, cursor.line, n.max(2) - 1),
+            Act::Case(case) if self.mode == Mode::VisualBlock => {
+                let carets = editor.with_buffer(|buffer| {
+                    self.block(buffer, cursor)
+                        .map_or(Vec::new(), |block| block.carets(buffer))
+                });
+                for caret in carets {
+                    let (start, end) = caret.bounds();
+                    convert(editor, start, end, case);
+                }
+                let (start, _) = ordered(self.anchor, cursor);
+                editor.set_cursor(start);
+                self.mode = Mode::Normal;
+            }
+            Act::Case(case) if visual => {
+                let range = editor.with_buffer(|buffer| self.selection(buffer, cursor, false));
+                convert(editor, range.start, range.end, case);
+                editor.set_cursor(range.start);
+                self.mode = Mode::Normal;
+            }
+            Act::Case(case) => {
+                let end = editor
+                    .with_buffer(|buffer| chars_after(line(buffer, cursor.line), cursor.index, n));
+                let end = Cursor::new(cursor.line, end);
+                convert(editor, cursor, end, case);
+                editor.set_cursor(end);
+            }
+            Act::Repeat => {
+                for input in with_count(&self.last_change, count) {
+                    self.input(input, editor, clipboard, effects, true);
+                }
+                if self.mode == Mode::Insert {
+                    self.leave_insert(editor, effects);
+                }
+            }
+            Act::CommandLine => {
+                self.command.clear();
+                self.visual_lines = None;
+                if visual {
+                    let (start, end) = ordered(self.anchor, cursor);
+                    self.visual_lines = Some((start.line, end.line));
+                    self.command.push_str("'<,'>");
+                }
+                self.mode = Mode::Command;
+            }
+        }
+    }
+
+    // where a motion takes the cursor, none if it can't move like f without a match
+    fn motion(
+        &mut self,
+        buffer: &Buffer,
+        cursor: Cursor,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<(Cursor, Reach)> {
+        let n = count.unwrap_or(1);
+        let text = line(buffer, cursor.line);
+        let last = buffer.lines.len() - 1;
+        let first_non_blank =
+            |line_i: usize| Cursor::new(line_i, indent(line(buffer, line_i)).len());
+
+        let vertical = |vim: &mut Self, line_i: usize| {
+            let column = vim
+                .column
+                .unwrap_or_else(|| cursors::column(text, cursor.index));
+            vim.column = Some(column);
+            let target = Cursor::new(line_i, cursors::char_index(line(buffer, line_i), column));
+            Some((target, Reach::Lines))
+        };
+        let half_page = || {
+            let lines = buffer.size().1.unwrap_or(0.0) / buffer.metrics().line_height;
+            (lines as usize / 2).max(1)
+        };
+
+        let target = match motion {
+            Motion::Up => return vertical(self, cursor.line.saturating_sub(n)),
+            Motion::Down => return vertical(self, cursor.line.saturating_add(n).min(last)),
+            Motion::HalfPageUp => return vertical(self, cursor.line.saturating_sub(half_page())),
+            Motion::HalfPageDown => {
+                return vertical(self, cursor.line.saturating_add(half_page()).min(last));
+            }
+            Motion::LineEnd => {
+                let line_i = cursor.line.saturating_add(n - 1).min(last);
+                self.column = Some(usize::MAX);
+                return Some((
+                    Cursor::new(line_i, line(buffer, line_i).len()),
+                    Reach::Inclusive,
+                ));
+            }
+            Motion::Left => {
+                let mut index = cursor.index;
+                for _ in 0..n {
+                    index = prev_char(text, index);
+                }
+                (Cursor::new(cursor.line, index), Reach::Exclusive)
+            }
+            Motion::Right => (
+                Cursor::new(cursor.line, chars_after(text, cursor.index, n)),
+                Reach::Exclusive,
+            ),
+            Motion::WordStart { big } => {
+                let mut target = cursor;
+                for _ in 0..n {
+                    target = word_start(buffer, target, big);
+                }
+                (target, Reach::Exclusive)
+            }
+            Motion::WordEnd { big } => {
+                let mut target = cursor;
+                for _ in 0..n {
+                    target = word_end(buffer, target, big);
+                }
+                (target, Reach::Inclusive)
+            }
+            Motion::WordBack { big } => {
+                let mut target = cursor;
+                for _ in 0..n {
+                    target = word_back(buffer, target, big);
+                }
+                (target, Reach::Exclusive)
+            }
+            Motion::LineStart => (Cursor::new(cursor.line, 0), Reach::Exclusive),
+            Motion::FirstNonBlank => (first_non_blank(cursor.line), Reach::Exclusive),
+            Motion::NextLine => (
+                first_non_blank(cursor.line.saturating_add(n).min(last)),
+                Reach::Lines,
+            ),
+            Motion::PreviousLine => (first_non_blank(cursor.line.saturating_sub(n)), Reach::Lines),
+            Motion::FirstLine => {
+                let line_i = count.map_or(0, |count| count - 1).min(last);
+                (first_non_blank(line_i), Reach::Lines)
+            }
+            Motion::LastLine => {
+                let line_i = count.map_or(last, |count| count - 1).min(last);
+                (first_non_blank(line_i), Reach::Lines)
+            }
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                find.target(text, cursor, n)?
+            }
+            Motion::RepeatFind { reverse } => {
+                let mut find = self.last_find?;
+                find.forward ^= reverse;
+                find.target(text, cursor, n)?
+            }
+            Motion::MatchingBracket => (matching_bracket(buffer, cursor)?, Reach::Inclusive),
+        };
+        self.column = None;
+        Some(target)
+    }
+
+    // the text an operator works on in normal mode
+    fn target_range(
+        &mut self,
+        buffer: &Buffer,
+        cursor: Cursor,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<Range> {
+        let n = count.unwrap_or(1);
+        match target {
+            Target::Lines => {
+                let end = cursor
+                    .line
+                    .saturating_add(n - 1)
+                    .min(buffer.lines.len() - 1);
+                Some(Range::lines(buffer, cursor.line, end))
+            }
+            Target::Object(object) => {
+                let (start, end) = object.find(buffer, cursor)?;
+                Some(Range {
+                    start,
+                    end,
+                    lines: false,
+                })
+            }
+            // cw changes to the end of the word, like ce
+            Target::Motion(Motion::WordStart { big })
+                if operator == Operator::Change && !at(buffer, cursor).is_whitespace() =>
+            {
+                let mut end = word_tail(buffer, cursor, big);
+                for _ in 1..n {
+                    end = word_end(buffer, end, big);
+                }
+                Some(Range::span(buffer, cursor, end, Reach::Inclusive))
+            }
+            Target::Motion(Motion::WordStart { big }) => {
+                let mut from = cursor;
+                for _ in 1..n {
+                    from = word_start(buffer, from, big);
+                }
+                let mut end = word_start(buffer, from, big);
+                // the last word of a line stops at the line end
+                if end.line > from.line {
+                    end = Cursor::new(from.line, line(buffer, from.line).len());
+                }
+                Some(Range::span(buffer, cursor, end, Reach::Exclusive))
+            }
+            Target::Motion(motion) => {
+                let (end, reach) = self.motion(buffer, cursor, motion, count)?;
+                Some(Range::span(buffer, cursor, end, reach))
+            }
+        }
+    }
+
+    // the visual selection, by lines if asked for, like D in visual mode
+    fn selection(&self, buffer: &Buffer, cursor: Cursor, lines: bool) -> Range {
+        let (start, end) = ordered(self.anchor, cursor);
+        if lines || self.mode != Mode::Visual {
+            return Range::lines(buffer, start.line, end.line);
+        }
+        Range {
+            start,
+            end: after(buffer, end),
+            lines: false,
+        }
+    }
+
+    fn operate(
+        &mut self,
+        operator: Operator,
+        range: Range,
+        register: Option<char>,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+        effects: &mut Vec<Effect>,
+    ) {
+        let cursor = editor.cursor();
+        let content = Register {
+            text: editor.with_buffer(|buffer| range.text(buffer)),
+            wise: if range.lines {
+                Wise::Lines
+            } else {
+                Wise::Chars
+            },
+        };
+        match operator {
+            Operator::Yank => {
+                store(register, content, true, clipboard);
+                let target = if !range.lines {
+                    range.start
+                } else if range.start.line < cursor.line {
+                    Cursor::new(range.start.line, cursor.index)
+                } else {
+                    cursor
+                };
+                editor.set_cursor(target);
+            }
+            Operator::Delete => {
+                store(register, content, false, clipboard);
+                if range.lines {
+                    delete_lines(editor, range.start.line, range.end.line);
+                } else {
+                    delete(editor, range.start, range.end);
+                }
+            }
+            Operator::Change => {
+                store(register, content, false, clipboard);
+                // changed lines keep the indent of the first one
+                let start = if range.lines {
+                    let indent =
+                        editor.with_buffer(|buffer| indent(line(buffer, range.start.line)).len());
+                    Cursor::new(range.start.line, indent)
+                } else {
+                    range.start
+                };
+                delete(editor, start, range.end);
+                self.enter_insert(1);
+                effects.push(Effect::Carets(Vec::new()));
+            }
+            Operator::Indent | Operator::Outdent => {
+                shift_lines(
+                    editor,
+                    range.start.line,
+                    range.end.line,
+                    operator == Operator::Indent,
+                );
+            }
+        }
+    }
+
+    fn operate_block(
+        &mut self,
+        operator: Operator,
+        register: Option<char>,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+        effects: &mut Vec<Effect>,
+    ) {
+        let cursor = editor.cursor();
+        let Some(block) = editor.with_buffer(|buffer| self.block(buffer, cursor)) else {
+            return;
+        };
+        let (text, carets) =
+            editor.with_buffer(|buffer| (block.text(buffer), block.carets(buffer)));
+        let content = Register {
+            text,
+            wise: Wise::Block,
+        };
+        self.mode = Mode::Normal;
+        effects.push(Effect::Block(None));
+
+        let (top, bottom) = block.lines();
+        let (left, _) = block.columns();
+        match operator {
+            Operator::Yank => store(register, content, true, clipboard),
+            Operator::Delete | Operator::Change => {
+                store(register, content, false, clipboard);
+                for caret in carets {
+                    let (start, end) = caret.bounds();
+                    delete(editor, start, end);
+                }
+            }
+            Operator::Indent | Operator::Outdent => {
+                shift_lines(editor, top, bottom, operator == Operator::Indent)
+            }
+        }
+        let start = editor
+            .with_buffer(|buffer| Cursor::new(top, cursors::char_index(line(buffer, top), left)));
+        editor.set_cursor(start);
+        if operator == Operator::Change {
+            self.block_insert(editor, block, false, effects);
+        }
+    }
+
+    // types at the left or right edge of the block on all its lines. lines too short
+    // for the left edge are left out
+    fn block_insert(
+        &mut self,
+        editor: &mut Editor<'_>,
+        block: Block,
+        after: bool,
+        effects: &mut Vec<Effect>,
+    ) {
+        let (top, bottom) = block.lines();
+        let (left, right) = block.columns();
+        let column = if after { right } else { left };
+        let mut positions = editor.with_buffer(|buffer| {
+            let bottom = bottom.min(buffer.lines.len() - 1);
+            (top..=bottom)
+                .filter_map(|line_i| {
+                    let text = line(buffer, line_i);
+                    (after || text.chars().count() >= column)
+                        .then(|| Cursor::new(line_i, cursors::char_index(text, column)))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+        });
+        effects.push(Effect::Block(None));
+        if let Some(first) = positions.next() {
+            editor.set_cursor(first);
+            let carets = positions
+                .map(|cursor| Caret::new(cursor, Selection::None))
+                .collect();
+            effects.push(Effect::Carets(carets));
+        }
+        self.enter_insert(1);
+    }
+
+    fn insert(
+        &mut self,
+        entry: Entry,
+        count: usize,
+        editor: &mut Editor<'_>,
+        effects: &mut Vec<Effect>,
+    ) {
+        let cursor = editor.cursor();
+        let text = editor.with_buffer(|buffer| line(buffer, cursor.line).to_string());
+
+        // I and A of a visual selection type before or after it
+        if self.mode != Mode::Normal {
+            let after = entry == Entry::LineEnd;
+            if self.mode == Mode::VisualBlock {
+                if let Some(block) = editor.with_buffer(|buffer| self.block(buffer, cursor)) {
+                    self.block_insert(editor, block, after, effects);
+                }
+                return;
+            }
+            let range = editor.with_buffer(|buffer| self.selection(buffer, cursor, false));
+            let at = match (after, range.lines) {
+                (false, false) => range.start,
+                (false, true) => editor.with_buffer(|buffer| {
+                    Cursor::new(
+                        range.start.line,
+                        indent(line(buffer, range.start.line)).len(),
+                    )
+                }),
+                (true, _) => range.end,
+            };
+            editor.set_cursor(at);
+            self.enter_insert(1);
+            return;
+        }
+
+        let at = match entry {
+            Entry::Before => cursor,
+            Entry::After => Cursor::new(cursor.line, next_char(&text, cursor.index)),
+            Entry::LineStart => Cursor::new(cursor.line, indent(&text).len()),
+            Entry::LineEnd => Cursor::new(cursor.line, text.len()),
+            // new lines keep the indent of the line they were opened from
+            Entry::Below => editor.insert_at(
+                Cursor::new(cursor.line, text.len()),
+                &format!("\n{}", indent(&text)),
+                None,
+            ),
+            Entry::Above => {
+                let indent = indent(&text);
+                editor.insert_at(Cursor::new(cursor.line, 0), &format!("{}\n", indent), None);
+                Cursor::new(cursor.line, indent.len())
+            }
+        };
+        editor.set_cursor(at);
+        let count = match entry {
+            Entry::Below | Entry::Above => 1,
+            _ => count,
+        };
+        self.enter_insert(count);
+    }
+
+    fn put(&mut self, content: &Register, before: bool, count: usize, editor: &mut Editor<'_>) {
+        let cursor = editor.cursor();
+        let text = editor.with_buffer(|buffer| line(buffer, cursor.line).to_string());
+        let at = if before {
+            cursor
+        } else {
+            Cursor::new(cursor.line, next_char(&text, cursor.index))
+        };
+
+        match content.wise {
+            Wise::Chars => {
+                let end = editor.insert_at(at, &content.text.repeat(count), None);
+                // on the last char put
+                let last =
+                    editor.with_buffer(|buffer| prev_char(line(buffer, end.line), end.index));
+                editor.set_cursor(Cursor::new(end.line, last));
+            }
+            Wise::Lines => {
+                let lines = content.text.strip_suffix('\n').unwrap_or(&content.text);
+                let lines = vec![lines; count].join("\n");
+                let line_i = if before {
+                    editor.insert_at(Cursor::new(cursor.line, 0), &format!("{}\n", lines), None);
+                    cursor.line
+                } else {
+                    editor.insert_at(
+                        Cursor::new(cursor.line, text.len()),
+                        &format!("\n{}", lines),
+                        None,
+                    );
+                    cursor.line + 1
+                };
+                let indent = editor.with_buffer(|buffer| indent(line(buffer, line_i)).len());
+                editor.set_cursor(Cursor::new(line_i, indent));
+            }
+            Wise::Block => {
+                editor.set_selection(Selection::None);
+                editor.set_cursor(at);
+                paste_block(editor, &content.text);
+                editor.set_cursor(at);
+            }
+        }
+    }
+
+    // replaces the selection, which goes to the unnamed register
+    fn put_selection(
+        &mut self,
+        register: Option<char>,
+        editor: &mut Editor<'_>,
+        clipboard: &mut dyn Clipboard,
+    ) {
+        let Some(content) = load(register, clipboard) else {
+            self.nothing_in(register);
+            return;
+        };
+        let cursor = editor.cursor();
+        let range = editor.with_buffer(|buffer| self.selection(buffer, cursor, false));
+        let replaced = Register {
+            text: editor.with_buffer(|buffer| range.text(buffer)),
+            wise: if range.lines {
+                Wise::Lines
+            } else {
+                Wise::Chars
+            },
+        };
+        store(None, replaced, false, clipboard);
+        self.mode = Mode::Normal;
+
+        if range.lines {
+            let last = editor.with_buffer(|buffer| buffer.lines.len() - 1);
+            delete_lines(editor, range.start.line, range.end.line);
+            let content = match content.wise {
+                Wise::Lines => content,
+                _ => Register {
+                    text: format!("{}\n", content.text),
+                    wise: Wise::Lines,
+                },
+            };
+            // the selection went to the end, the lines go below what is left
+            let before = range.end.line < last || range.start.line == 0;
+            self.put(&content, before, 1, editor);
+        } else {
+            delete(editor, range.start, range.end);
+            let content = match content.wise {
+                Wise::Lines => Register {
+                    text: format!("\n{}", content.text),
+                    wise: Wise::Chars,
+                },
+                _ => content,
+            };
+            self.put(&content, true, 1, editor);
+        }
+    }
+
+    fn nothing_in(&mut self, register: Option<char>) {
+        self.message = Some(format!(
+            "E353: Nothing in register {}",
+            register.unwrap_or('"')
+        ));
+    }
+
+    // ex commands: [range]s/pattern/replacement/[gi], w, q, wq, x and a line number
+    fn ex(&mut self, command: &str, editor: &mut Editor<'_>, effects: &mut Vec<Effect>) {
+        let cursor = editor.cursor();
+        let last = editor.with_buffer(|buffer| buffer.lines.len() - 1);
+        let (range, rest) = parse_range(command.trim(), cursor.line, last, self.visual_lines);
+        let rest = rest.trim();
+
+        match rest {
+            "" => {
+                if let Some((_, line_i)) = range {
+                    let target = editor.with_buffer(|buffer| {
+                        Cursor::new(line_i, indent(line(buffer, line_i)).len())
+                    });
+                    editor.set_cursor(target);
+                }
+            }
+            "w" | "w!" | "write" => effects.push(Effect::Publish(Message::SaveFile)),
+            "q" | "quit" => effects.push(Effect::Publish(Message::TabCloseCurrent)),
+            "q!" | "quit!" => effects.push(Effect::Publish(Message::TabDiscardCurrent)),
+            "wq" | "wq!" | "x" | "x!" => {
+                effects.push(Effect::Publish(Message::SaveFile));
+                effects.push(Effect::Publish(Message::TabCloseCurrent));
+            }
+            _ if rest.starts_with('s') && rest[1..].starts_with(|c: char| !c.is_alphanumeric()) => {
+                let range = range.unwrap_or((cursor.line, cursor.line));
+                self.substitute(range, &rest[1..], editor);
+            }
+            _ => self.message = Some(format!("E492: Not an editor command: {}", command)),
+        }
+    }
+
+    // the pattern is a regex, \1 and & in the replacement are groups
+    fn substitute(&mut self, (first, last): (usize, usize), args: &str, editor: &mut Editor<'_>) {
+        let mut chars = args.chars();
+        let Some(delimiter) = chars.next().filter(|c| !c.is_whitespace()) else {
+            self.message = Some("E35: No previous regular expression".to_string());
+            return;
+        };
+        let parts = split_unescaped(chars.as_str(), delimiter);
+        let pattern = parts.first().map_or("", String::as_str);
+        let replacement = replacement(parts.get(1).map_or("", String::as_str));
+        let flags = parts.get(2).map_or("", String::as_str);
+        let regex = match RegexBuilder::new(pattern)
+            .case_insensitive(flags.contains('i'))
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(_) => {
+                self.message = Some(format!("E486: Invalid pattern: {}", pattern));
+                return;
+            }
+        };
+        let global = flags.contains('g');
+
+        let mut substitutions = 0;
+        let mut lines = 0;
+        let mut last_changed = None;
+        // bottom up, a replacement may add lines
+        for line_i in (first..=last).rev() {
+            let text = editor.with_buffer(|buffer| line(buffer, line_i).to_string());
+            let found = regex.find_iter(&text).count();
+            if found == 0 {
+                continue;
+            }
+            let new = if global {
+                regex.replace_all(&text, replacement.as_str())
+            } else {
+                regex.replace(&text, replacement.as_str())
+            };
+            substitutions += if global { found } else { 1 };
+            lines += 1;
+            last_changed.get_or_insert(line_i);
+            editor.delete_range(Cursor::new(line_i, 0), Cursor::new(line_i, text.len()));
+            editor.insert_at(Cursor::new(line_i, 0), &new, None);
+        }
+
+        let Some(line_i) = last_changed else {
+            self.message = Some(format!("E486: Pattern not found: {}", pattern));
+            return;
+        };
+        let target = editor.with_buffer(|buffer| {
+            let line_i = line_i.min(buffer.lines.len() - 1);
+            Cursor::new(line_i, indent(line(buffer, line_i)).len())
+        });
+        editor.set_cursor(target);
+        if lines > 1 {
+            self.message = Some(format!(
+                "{} substitutions on {} lines",
+                substitutions, lines
+            ));
+        }
+    }
+}
+
+// a command of normal or visual mode
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    act: Act,
+}
+
+impl Command {
+    // changes are repeated by .
+    fn changes(&self) -> bool {
+        match self.act {
+            Act::Operate { operator, .. } => operator != Operator::Yank,
+            Act::Put { .. } | Act::Insert(_) | Act::Replace(_) | Act::Join | Act::Case(_) => true,
+            _ => false,
+        }
+    }
+}
+
+enum Act {
+    Move(Motion),
+    Operate {
+        operator: Operator,
+        target: Target,
+        // count between the operator and its motion, like the 3 of d3w
+        count: Option<usize>,
+    },
+    // visual mode, by lines if true
+    OperateSelection(Operator, bool),
+    SelectObject(Object),
+    Put {
+        before: bool,
+    },
+    Undo,
+    Redo,
+    Insert(Entry),
+    // enters the visual mode, or leaves it if already in it
+    Visual(Mode),
+    SwapAnchor,
+    ExitVisual,
+    Replace(char),
+    Join,
+    Case(Case),
+    Repeat,
+    CommandLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Motion(Motion),
+    Object(Object),
+    // the operator doubled, like dd
+    Lines,
+}
+
+// where insert mode starts: i a I A o O
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Entry {
+    Before,
+    After,
+    LineStart,
+    LineEnd,
+    Below,
+    Above,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Case {
+    Toggle,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart { big: bool },
+    WordEnd { big: bool },
+    WordBack { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    NextLine,
+    PreviousLine,
+    FirstLine,
+    LastLine,
+    Find(Find),
+    RepeatFind { reverse: bool },
+    MatchingBracket,
+    HalfPageDown,
+    HalfPageUp,
+}
+
+// how much of the text between the cursor and a motion's target is taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reach {
+    Exclusive,
+    Inclusive,
+    Lines,
+}
+
+// f, F, t and T
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Find {
+    forward: bool,
+    // stops before the char
+    till: bool,
+    target: char,
+}
+
+impl Find {
+    fn target(&self, text: &str, cursor: Cursor, count: usize) -> Option<(Cursor, Reach)> {
+        let at = |index| Cursor::new(cursor.line, index);
+        if self.forward {
+            let (index, _) = text[cursor.index..]
+                .char_indices()
+                .skip(1)
+                .filter(|(_, c)| *c == self.target)
+                .nth(count - 1)?;
+            let index = cursor.index + index;
+            let index = if self.till {
+                prev_char(text, index)
+            } else {
+                index
+            };
+            Some((at(index), Reach::Inclusive))
+        } else {
+            let (index, _) = text[..cursor.index]
+                .char_indices()
+                .rev()
+                .filter(|(_, c)| *c == self.target)
+                .nth(count - 1)?;
+            let index = if self.till {
+                next_char(text, index)
+            } else {
+                index
+            };
+            Some((at(index), Reach::Exclusive))
+        }
+    }
+}
+
+// text objects like iw, a" and i(
+#[derive(Debug, Clone, Copy)]
+struct Object {
+    kind: ObjectKind,
+    // with the surrounding quotes, brackets or blanks
+    around: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ObjectKind {
+    Word { big: bool },
+    Quote(char),
+    Bracket(char, char),
+}
+
+impl Object {
+    fn new(c: char, around: bool) -> Option<Self> {
+        let kind = match c {
+            'w' => ObjectKind::Word { big: false },
+            'W' => ObjectKind::Word { big: true },
+            '"' | '\'' | '`' => ObjectKind::Quote(c),
+            '(' | ')' | 'b' => ObjectKind::Bracket('(', ')'),
+            '[' | ']' => ObjectKind::Bracket('[', ']'),
+            '{' | '}' | 'B' => ObjectKind::Bracket('{', '}'),
+            '<' | '>' => ObjectKind::Bracket('<', '>'),
+            _ => return None,
+        };
+        Some(Self { kind, around })
+    }
+
+    // (start, end) with the end excluded
+    fn find(&self, buffer: &Buffer, cursor: Cursor) -> Option<(Cursor, Cursor)> {
+        let text = line(buffer, cursor.line);
+        let at = |index| Cursor::new(cursor.line, index);
+        match self.kind {
+            ObjectKind::Word { big } => {
+                let (start, end) = word_object(text, cursor.index, big, self.around)?;
+                Some((at(start), at(end)))
+            }
+            ObjectKind::Quote(quote) => {
+                let (start, end) = quote_object(text, cursor.index, quote, self.around)?;
+                Some((at(start), at(end)))
+            }
+            ObjectKind::Bracket(open, close) => {
+                bracket_object(buffer, cursor, open, close, self.around)
+            }
+        }
+    }
+}
+
+// text from start to end, the end excluded. by lines it spans whole lines
+struct Range {
+    start: Cursor,
+    end: Cursor,
+    lines: bool,
+}
+
+impl Range {
+    fn lines(buffer: &Buffer, first: usize, last: usize) -> Self {
+        Self {
+            start: Cursor::new(first, 0),
+            end: Cursor::new(last, line(buffer, last).len()),
+            lines: true,
+        }
+    }
+
+    // from the cursor to a motion's target
+    fn span(buffer: &Buffer, from: Cursor, to: Cursor, reach: Reach) -> Self {
+        let (start, end) = ordered(from, to);
+        match reach {
+            Reach::Lines => Self::lines(buffer, start.line, end.line),
+            Reach::Inclusive => Self {
+                start,
+                end: Cursor::new(end.line, next_char(line(buffer, end.line), end.index)),
+                lines: false,
+            },
+            Reach::Exclusive => Self {
+                start,
+                end,
+                lines: false,
+            },
+        }
+    }
+
+    // lines end in a newline
+    fn text(&self, buffer: &Buffer) -> String {
+        let mut text = text_between(buffer, self.start, self.end);
+        if self.lines {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// how a register was filled, and is put back
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wise {
+    Chars,
+    Lines,
+    Block,
+}
+
+#[derive(Debug, Clone)]
+struct Register {
+    text: String,
+    wise: Wise,
+}
+
+fn is_register(name: char) -> bool {
+    name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | '_' | '+' | '*')
+}
+
+// a-z sets, A-Z appends, + and * are the clipboard and _ drops. without a name
+// yanks go to 0, deleted lines shift through 1-9 and smaller deletes go to -
+fn store(register: Option<char>, content: Register, yank: bool, clipboard: &mut dyn Clipboard) {
+    let name = register.unwrap_or('"');
+    if name == '_' {
+        return;
+    }
+    let mut registers = REGISTERS.lock().unwrap();
+    let content = match name {
+        'A'..='Z' => {
+            let appended = registers
+                .entry(name.to_ascii_lowercase())
+                .or_insert(Register {
+                    text: String::new(),
+                    wise: content.wise,
+                });
+            if content.wise == Wise::Lines && appended.wise != Wise::Lines {
+                appended.text.push('\n');
+                appended.wise = Wise::Lines;
+            }
+            appended.text.push_str(&content.text);
+            appended.clone()
+        }
+        'a'..='z' | '0'..='9' | '-' => {
+            registers.insert(name, content.clone());
+            content
+        }
+        '+' | '*' => {
+            clipboard.write(Standard, content.text.clone());
+            content
+        }
+        _ if yank => {
+            registers.insert('0', content.clone());
+            content
+        }
+        _ if content.wise == Wise::Lines || content.text.contains('\n') => {
+            for n in (1..9).rev() {
+                let from = char::from_digit(n, 10).unwrap();
+                if let Some(shifted) = registers.remove(&from) {
+                    registers.insert(char::from_digit(n + 1, 10).unwrap(), shifted);
+                }
+            }
+            registers.insert('1', content.clone());
+            content
+        }
+        _ => {
+            registers.insert('-', content.clone());
+            content
+        }
+    };
+    registers.insert('"', content);
+}
+
+fn load(register: Option<char>, clipboard: &mut dyn Clipboard) -> Option<Register> {
+    match register.unwrap_or('"') {
+        '+' | '*' => clipboard.read(Standard).map(|text| Register {
+            wise: if text.ends_with('\n') {
+                Wise::Lines
+            } else {
+                Wise::Chars
+            },
+            text,
+        }),
+        name => REGISTERS
+            .lock()
+            .unwrap()
+            .get(&name.to_ascii_lowercase())
+            .cloned(),
+    }
+}
+
+enum Parse {
+    // more keys to come
+    Incomplete,
+    Invalid,
+}
+
+struct Keys<'a> {
+    keys: &'a [Input],
+}
+
+impl Keys<'_> {
+    fn next(&mut self) -> Result<Input, Parse> {
+        let (first, rest) = self.keys.split_first().ok_or(Parse::Incomplete)?;
+        self.keys = rest;
+        Ok(*first)
+    }
+
+    fn char(&mut self) -> Result<char, Parse> {
+        match self.next()? {
+            Input::Char(c) => Ok(c),
+            _ => Err(Parse::Invalid),
+        }
+    }
+
+    // a leading 0 is the motion to the line start, not a count. at most MAX_COUNT
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+        while let Some(Input::Char(c @ '0'..='9')) = self.keys.first() {
+            if *c == '0' && count.is_none() {
+                break;
+            }
+            let digit = c.to_digit(10).unwrap_or_default() as usize;
+            count = Some(
+                count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(digit)
+                    .min(MAX_COUNT),
+            );
+            self.keys = &self.keys[1..];
+        }
+        count
+    }
+}
+
+// ["x][count]command, operators take [count]motion, a text object or themselves
+fn parse(keys: &[Input], visual: bool) -> Result<Command, Parse> {
+    let mut keys = Keys { keys };
+    let mut register = None;
+    if keys.keys.first() == Some(&Input::Char('"')) {
+        keys.next()?;
+        let name = keys.char()?;
+        if !is_register(name) {
+            return Err(Parse::Invalid);
+        }
+        register = Some(name);
+    }
+    let count = keys.count();
+    let key = keys.next()?;
+
+    let act = if let Some(operator) = operator(key) {
+        if visual {
+            Act::OperateSelection(operator, false)
+        } else {
+            let inner = keys.count();
+            let next = keys.next()?;
+            let target = match next {
+                _ if next == key => Target::Lines,
+                Input::Char(c @ ('i' | 'a')) => {
+                    Target::Object(Object::new(keys.char()?, c == 'a').ok_or(Parse::Invalid)?)
+                }
+                _ => Target::Motion(motion(next, &mut keys)?.ok_or(Parse::Invalid)?),
+            };
+            Act::Operate {
+                operator,
+                target,
+                count: inner,
+            }
+        }
+    } else if let Some(motion) = motion(key, &mut keys)? {
+        Act::Move(motion)
+    } else {
+        command(key, &mut keys, visual)?
+    };
+    Ok(Command {
+        register,
+        count,
+        act,
+    })
+}
+
+fn operator(key: Input) -> Option<Operator> {
+    match key {
+        Input::Char('d') => Some(Operator::Delete),
+        Input::Char('c') => Some(Operator::Change),
+        Input::Char('y') => Some(Operator::Yank),
+        Input::Char('>') => Some(Operator::Indent),
+        Input::Char('<') => Some(Operator::Outdent),
+        _ => None,
+    }
+}
+
+fn motion(key: Input, keys: &mut Keys<'_>) -> Result<Option<Motion>, Parse> {
+    let motion = match key {
+        Input::Char('h') | Input::Left | Input::Backspace => Motion::Left,
+        Input::Char('l') | Input::Char(' ') | Input::Right => Motion::Right,
+        Input::Char('j') | Input::Down => Motion::Down,
+        Input::Char('k') | Input::Up => Motion::Up,
+        Input::Char('w') => Motion::WordStart { big: false },
+        Input::Char('W') => Motion::WordStart { big: true },
+        Input::Char('e') => Motion::WordEnd { big: false },
+        Input::Char('E') => Motion::WordEnd { big: true },
+        Input::Char('b') => Motion::WordBack { big: false },
+        Input::Char('B') => Motion::WordBack { big: true },
+        Input::Char('0') | Input::Home => Motion::LineStart,
+        Input::Char('^') => Motion::FirstNonBlank,
+        Input::Char('$') | Input::End => Motion::LineEnd,
+        Input::Char('G') => Motion::LastLine,
+        Input::Char('g') => match keys.char()? {
+            'g' => Motion::FirstLine,
+            _ => return Err(Parse::Invalid),
+        },
+        Input::Char(c @ ('f' | 'F' | 't' | 'T')) => Motion::Find(Find {
+            forward: c.is_lowercase(),
+            till: c.eq_ignore_ascii_case(&'t'),
+            target: keys.char()?,
+        }),
+        Input::Char(';') => Motion::RepeatFind { reverse: false },
+        Input::Char(',') => Motion::RepeatFind { reverse: true },
+        Input::Char('%') => Motion::MatchingBracket,
+        Input::Enter | Input::Char('+') => Motion::NextLine,
+        Input::Char('-') => Motion::PreviousLine,
+        Input::Ctrl('d') => Motion::HalfPageDown,
+        Input::Ctrl('u') => Motion::HalfPageUp,
+        _ => return Ok(None),
+    };
+    Ok(Some(motion))
+}
+
+// commands besides operators and motions, some differ in visual mode
+fn command(key: Input, keys: &mut Keys<'_>, visual: bool) -> Result<Act, Parse> {
+    let operate = |operator, motion| Act::Operate {
+        operator,
+        target: Target::Motion(motion),
+        count: None,
+    };
+    let act = match key {
+        Input::Char('x') | Input::Delete if visual => {
+            Act::OperateSelection(Operator::Delete, false)
+        }
+        Input::Char('x') | Input::Delete => operate(Operator::Delete, Motion::Right),
+        Input::Char('X' | 'D') if visual => Act::OperateSelection(Operator::Delete, true),
+        Input::Char('X') => operate(Operator::Delete, Motion::Left),
+        Input::Char('D') => operate(Operator::Delete, Motion::LineEnd),
+        Input::Char('C' | 'S' | 'R') if visual => Act::OperateSelection(Operator::Change, true),
+        Input::Char('C') => operate(Operator::Change, Motion::LineEnd),
+        Input::Char('s') if visual => Act::OperateSelection(Operator::Change, false),
+        Input::Char('s') => operate(Operator::Change, Motion::Right),
+        Input::Char('S') => Act::Operate {
+            operator: Operator::Change,
+            target: Target::Lines,
+            count: None,
+        },
+        Input::Char('Y') if visual => Act::OperateSelection(Operator::Yank, true),
+        Input::Char('Y') => Act::Operate {
+            operator: Operator::Yank,
+            target: Target::Lines,
+            count: None,
+        },
+        Input::Char('p') => Act::Put { before: false },
+        Input::Char('P') => Act::Put { before: true },
+        Input::Char('u') if visual => Act::Case(Case::Lower),
+        Input::Char('U') if visual => Act::Case(Case::Upper),
+        Input::Char('~') => Act::Case(Case::Toggle),
+        Input::Char('u') => Act::Undo,
+        Input::Ctrl('r') => Act::Redo,
+        Input::Char(c @ ('i' | 'a')) if visual => {
+            Act::SelectObject(Object::new(keys.char()?, c == 'a').ok_or(Parse::Invalid)?)
+        }
+        Input::Char('i') => Act::Insert(Entry::Before),
+        Input::Char('a') => Act::Insert(Entry::After),
+        Input::Char('I') => Act::Insert(Entry::LineStart),
+        Input::Char('A') => Act::Insert(Entry::LineEnd),
+        Input::Char('o' | 'O') if visual => Act::SwapAnchor,
+        Input::Char('o') => Act::Insert(Entry::Below),
+        Input::Char('O') => Act::Insert(Entry::Above),
+        Input::Char('v') => Act::Visual(Mode::Visual),
+        Input::Char('V') => Act::Visual(Mode::VisualLine),
+        Input::Ctrl('v') => Act::Visual(Mode::VisualBlock),
+        Input::Char('r') if !visual => Act::Replace(keys.char()?),
+        Input::Char('J') => Act::Join,
+        Input::Char('.') if !visual => Act::Repeat,
+        Input::Char(':') => Act::CommandLine,
+        Input::Escape if visual => Act::ExitVisual,
+        _ => return Err(Parse::Invalid),
+    };
+    Ok(act)
+}
+
+// the keys of a change with another count, like 3. after dw types 3dw
+fn with_count(keys: &[Input], count: Option<usize>) -> Vec<Input> {
+    let Some(count) = count else {
+        return keys.to_vec();
+    };
+    let register = if keys.first() == Some(&Input::Char('"')) {
+        keys.len().min(2)
+    } else {
+        0
+    };
+    let mut rest = &keys[register..];
+    if let Some(Input::Char('1'..='9')) = rest.first() {
+        while let Some(Input::Char('0'..='9')) = rest.first() {
+            rest = &rest[1..];
+        }
+    }
+    keys[..register]
+        .iter()
+        .copied()
+        .chain(count.to_string().chars().map(Input::Char))
+        .chain(rest.iter().copied())
+        .collect()
+}
+
+// types a key of insert mode again, for . and counts
+fn type_input(editor: &mut Editor<'_>, input: Input) {
+    let motion = |motion| cosmic_text::Action::Motion(motion);
+    match input {
+        Input::Char(c) => editor.insert_string(&c.to_string(), None),
+        Input::Enter => editor.action(cosmic_text::Action::Enter),
+        Input::Backspace => editor.action(cosmic_text::Action::Backspace),
+        Input::Delete => editor.action(cosmic_text::Action::Delete),
+        Input::Tab => editor.insert_string(INDENT, None),
+        Input::Left => editor.action(motion(cosmic_text::Motion::Left)),
+        Input::Right => editor.action(motion(cosmic_text::Motion::Right)),
+        Input::Up => editor.action(motion(cosmic_text::Motion::Up)),
+        Input::Down => editor.action(motion(cosmic_text::Motion::Down)),
+        Input::Home => editor.action(motion(cosmic_text::Motion::Home)),
+        Input::End => editor.action(motion(cosmic_text::Motion::End)),
+        Input::Ctrl(_) | Input::Escape => {}
+    }
+}
+
+fn delete(editor: &mut Editor<'_>, start: Cursor, end: Cursor) {
+    if start != end {
+        editor.delete_range(start, end);
+    }
+    editor.set_cursor(start);
+}
+
+// deletes whole lines, the cursor goes to the line taking their place
+fn delete_lines(editor: &mut Editor<'_>, first: usize, last: usize) {
+    let (count, last_len, above_len) = editor.with_buffer(|buffer| {
+        let above = first.checked_sub(1).map(|above| line(buffer, above).len());
+        (buffer.lines.len(), line(buffer, last).len(), above)
+    });
+    if last + 1 < count {
+        editor.delete_range(Cursor::new(first, 0), Cursor::new(last + 1, 0));
+    } else if let Some(above_len) = above_len {
+        editor.delete_range(
+            Cursor::new(first - 1, above_len),
+            Cursor::new(last, last_len),
+        );
+    } else {
+        editor.delete_range(Cursor::new(0, 0), Cursor::new(last, last_len));
+    }
+    let target = editor.with_buffer(|buffer| {
+        let line_i = first.min(buffer.lines.len() - 1);
+        Cursor::new(line_i, indent(line(buffer, line_i)).len())
+    });
+    editor.set_cursor(target);
+}
+
+// > and <, blank lines are not indented
+fn shift_lines(editor: &mut Editor<'_>, first: usize, last: usize, indent_more: bool) {
+    for line_i in first..=last {
+        let text = editor.with_buffer(|buffer| line(buffer, line_i).to_string());
+        if indent_more {
+            if !text.is_empty() {
+                editor.insert_at(Cursor::new(line_i, 0), INDENT, None);
+            }
+        } else {
+            let spaces = text.len() - text.trim_start_matches(' ').len();
+            let spaces = spaces.min(INDENT.len());
+            if spaces > 0 {
+                editor.delete_range(Cursor::new(line_i, 0), Cursor::new(line_i, spaces));
+            }
+        }
+    }
+    let target = editor.with_buffer(|buffer| Cursor::new(first, indent(line(buffer, first)).len()));
+    editor.set_cursor(target);
+}
+
+// J, the next lines' indent becomes a single space
+fn join(editor: &mut Editor<'_>, line_i: usize, times: usize) {
+    for _ in 0..times {
+        let Some((text, next)) = editor.with_buffer(|buffer| {
+            (line_i + 1 < buffer.lines.len()).then(|| {
+                (
+                    line(buffer, line_i).to_string(),
+                    line(buffer, line_i + 1).to_string(),
+                )
+            })
+        }) else {
+            break;
+        };
+        let trimmed = next.trim_start();
+        let space = !(text.is_empty()
+            || text.ends_with(' ')
+            || trimmed.is_empty()
+            || trimmed.starts_with(')'));
+        let joint = Cursor::new(line_i, text.len());
+        editor.delete_range(joint, Cursor::new(line_i + 1, next.len() - trimmed.len()));
+        if space {
+            editor.insert_at(joint, " ", None);
+        }
+        editor.set_cursor(joint);
+    }
+}
+
+fn convert(editor: &mut Editor<'_>, start: Cursor, end: Cursor, case: Case) {
+    let text = editor.with_buffer(|buffer| text_between(buffer, start, end));
+    let converted: String = match case {
+        Case::Lower => text.to_lowercase(),
+        Case::Upper => text.to_uppercase(),
+        Case::Toggle => text
+            .chars()
+            .flat_map(|c| {
+                if c.is_uppercase() {
+                    c.to_lowercase().collect::<Vec<_>>()
+                } else {
+                    c.to_uppercase().collect()
+                }
+            })
+            .collect(),
+    };
+    if converted != text {
+        editor.delete_range(start, end);
+        editor.insert_at(start, &converted, None);
+    }
+}
+
+// "%", "3", ".,$" or "'<,'>" before an ex command, as line indices
+fn parse_range(
+    command: &str,
+    current: usize,
+    last: usize,
+    visual: Option<(usize, usize)>,
+) -> (Option<(usize, usize)>, &str) {
+    if let Some(rest) = command.strip_prefix('%') {
+        return (Some((0, last)), rest);
+    }
+    let Some((first, rest)) = address(command, current, last, visual) else {
+        return (None, command);
+    };
+    if let Some(rest) = rest.strip_prefix(',')
+        && let Some((second, rest)) = address(rest, current, last, visual)
+    {
+        return (Some((first.min(second), first.max(second))), rest);
+    }
+    (Some((first, first)), rest)
+}
+
+fn address(
+    text: &str,
+    current: usize,
+    last: usize,
+    visual: Option<(usize, usize)>,
+) -> Option<(usize, &str)> {
+    if let Some(rest) = text.strip_prefix('.') {
+        return Some((current, rest));
+    }
+    if let Some(rest) = text.strip_prefix('$') {
+        return Some((last, rest));
+    }
+    if let Some(rest) = text.strip_prefix("'<") {
+        return Some((visual?.0, rest));
+    }
+    if let Some(rest) = text.strip_prefix("'>") {
+        return Some((visual?.1, rest));
+    }
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let number: usize = text[..digits].parse().ok()?;
+    Some((number.saturating_sub(1).min(last), &text[digits..]))
+}
+
+// parts of s/a/b/g, \ escapes the delimiter
+fn split_unescaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().unwrap();
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => part.push(next),
+                Some(next) => {
+                    part.push(c);
+                    part.push(next);
+                }
+                None => part.push(c),
+            },
+            _ if c == delimiter => parts.push(String::new()),
+            _ => part.push(c),
+        }
+    }
+    parts
+}
+
+// vim's replacement to the regex crate's: & and \0-\9 are groups, \r a new line
+fn replacement(text: &str) -> String {
+    let mut replacement = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => replacement.push_str(&format!("${{{}}}", digit)),
+                Some('r' | 'n') => replacement.push('\n'),
+                Some('t') => replacement.push('\t'),
+                Some('$') => replacement.push_str("$$"),
+                Some(next) => replacement.push(next),
+                None => replacement.push('\\'),
+            },
+            '&' => replacement.push_str("${0}"),
+            '$' => replacement.push_str("$$"),
+            _ => replacement.push(c),
+        }
+    }
+    replacement
+}
+
+fn line(buffer: &Buffer, line_i: usize) -> &str {
+    buffer.lines[line_i].text()
+}
+
+fn indent(text: &str) -> &str {
+    &text[..text.len() - text.trim_start().len()]
+}
+
+fn next_char(text: &str, index: usize) -> usize {
+    text[index..]
+        .chars()
+        .next()
+        .map_or(index, |c| index + c.len_utf8())
+}
+
+fn prev_char(text: &str, index: usize) -> usize {
+    text[..index]
+        .chars()
+        .next_back()
+        .map_or(index, |c| index - c.len_utf8())
+}
+
+// index n chars further, at most the line end
+fn chars_after(text: &str, index: usize, n: usize) -> usize {
+    text[index..]
+        .char_indices()
+        .nth(n)
+        .map_or(text.len(), |(offset, _)| index + offset)
+}
+
+fn ordered(a: Cursor, b: Cursor) -> (Cursor, Cursor) {
+    if position(&b) < position(&a) {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+// on the last char instead of past it, an empty line has none
+fn on_char(buffer: &Buffer, cursor: Cursor) -> Cursor {
+    let line_i = cursor.line.min(buffer.lines.len() - 1);
+    let text = line(buffer, line_i);
+    let mut index = cursor.index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    if index == text.len() {
+        index = prev_char(text, index);
+    }
+    Cursor::new(line_i, index)
+}
+
+// past the char at the cursor, the line break included at a line end
+fn after(buffer: &Buffer, cursor: Cursor) -> Cursor {
+    let text = line(buffer, cursor.line);
+    if cursor.index < text.len() {
+        Cursor::new(cursor.line, next_char(text, cursor.index))
+    } else {
+        next(buffer, cursor).unwrap_or(cursor)
+    }
+}
+
+fn text_between(buffer: &Buffer, start: Cursor, end: Cursor) -> String {
+    if start.line == end.line {
+        return line(buffer, start.line)[start.index..end.index].to_string();
+    }
+    let mut text = line(buffer, start.line)[start.index..].to_string();
+    for line_i in start.line + 1..end.line {
+        text.push('\n');
+        text.push_str(line(buffer, line_i));
+    }
+    text.push('\n');
+    text.push_str(&line(buffer, end.line)[..end.index]);
+    text
+}
+
+// the buffer walked char by char, a line end is a '\n' of its own
+fn next(buffer: &Buffer, cursor: Cursor) -> Option<Cursor> {
+    let text = line(buffer, cursor.line);
+    if cursor.index < text.len() {
+        Some(Cursor::new(cursor.line, next_char(text, cursor.index)))
+    } else if cursor.line + 1 < buffer.lines.len() {
+        Some(Cursor::new(cursor.line + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn prev(buffer: &Buffer, cursor: Cursor) -> Option<Cursor> {
+    if cursor.index > 0 {
+        let text = line(buffer, cursor.line);
+        Some(Cursor::new(cursor.line, prev_char(text, cursor.index)))
+    } else if cursor.line > 0 {
+        Some(Cursor::new(
+            cursor.line - 1,
+            line(buffer, cursor.line - 1).len(),
+        ))
+    } else {
+        None
+    }
+}
+
+fn at(buffer: &Buffer, cursor: Cursor) -> char {
+    line(buffer, cursor.line)[cursor.index..]
+        .chars()
+        .next()
+        .unwrap_or('\n')
+}
+
+fn end_of(buffer: &Buffer) -> Cursor {
+    let last = buffer.lines.len() - 1;
+    Cursor::new(last, line(buffer, last).len())
+}
+
+// blanks, punctuation and word chars. big words are anything but blanks
+fn class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        2
+    } else {
+        1
+    }
+}
+
+fn class_at(buffer: &Buffer, cursor: Cursor, big: bool) -> u8 {
+    class(at(buffer, cursor), big)
+}
+
+fn empty_line(buffer: &Buffer, cursor: Cursor) -> bool {
+    line(buffer, cursor.line).is_empty()
+}
+
+// w, an empty line counts as a word
+fn word_start(buffer: &Buffer, cursor: Cursor, big: bool) -> Cursor {
+    let mut cursor = cursor;
+    let start = class_at(buffer, cursor, big);
+    loop {
+        match next(buffer, cursor) {
+            Some(next) => cursor = next,
+            None => return end_of(buffer),
+        }
+        if start == 0 || class_at(buffer, cursor, big) != start {
+            break;
+        }
+    }
+    while class_at(buffer, cursor, big) == 0 && !empty_line(buffer, cursor) {
+        match next(buffer, cursor) {
+            Some(next) => cursor = next,
+            None => return end_of(buffer),
+        }
+    }
+    cursor
+}
+
+// e
+fn word_end(buffer: &Buffer, cursor: Cursor, big: bool) -> Cursor {
+    let Some(mut cursor) = next(buffer, cursor) else {
+        return cursor;
+    };
+    while class_at(buffer, cursor, big) == 0 {
+        match next(buffer, cursor) {
+            Some(next) => cursor = next,
+            None => return cursor,
+        }
+    }
+    word_tail(buffer, cursor, big)
+}
+
+// the last char of the word at the cursor
+fn word_tail(buffer: &Buffer, cursor: Cursor, big: bool) -> Cursor {
+    let class = class_at(buffer, cursor, big);
+    let mut cursor = cursor;
+    while let Some(next) = next(buffer, cursor) {
+        if class_at(buffer, next, big) != class {
+            break;
+        }
+        cursor = next;
+    }
+    cursor
+}
+
+// b, an empty line counts as a word
+fn word_back(buffer: &Buffer, cursor: Cursor, big: bool) -> Cursor {
+    let Some(mut cursor) = prev(buffer, cursor) else {
+        return cursor;
+    };
+    while class_at(buffer, cursor, big) == 0 && !empty_line(buffer, cursor) {
+        match prev(buffer, cursor) {
+            Some(prev) => cursor = prev,
+            None => return cursor,
+        }
+    }
+    let class = class_at(buffer, cursor, big);
+    if class == 0 {
+        return cursor;
+    }
+    while let Some(prev) = prev(buffer, cursor) {
+        if class_at(buffer, prev, big) != class {
+            break;
+        }
+        cursor = prev;
+    }
+    cursor
+}
+
+// iw and aw, within the line
+fn word_object(text: &str, index: usize, big: bool, around: bool) -> Option<(usize, usize)> {
+    if text.is_empty() {
+        return None;
+    }
+    let class_at = |index: usize| text[index..].chars().next().map(|c| class(c, big));
+    let index = index.min(prev_char(text, text.len()));
+    let word = class_at(index);
+
+    let mut start = index;
+    while start > 0 && class_at(prev_char(text, start)) == word {
+        start = prev_char(text, start);
+    }
+    let mut end = next_char(text, index);
+    while end < text.len() && class_at(end) == word {
+        end = next_char(text, end);
+    }
+
+    if around {
+        let mut blanks = end;
+        while blanks < text.len() && class_at(blanks) == Some(0) {
+            blanks = next_char(text, blanks);
+        }
+        if word == Some(0) {
+            // blanks take the word after them
+            let next = class_at(end);
+            while end < text.len() && class_at(end) == next {
+                end = next_char(text, end);
+            }
+        } else if blanks > end {
+            end = blanks;
+        } else {
+            while start > 0 && class_at(prev_char(text, start)) == Some(0) {
+                start = prev_char(text, start);
+            }
+        }
+    }
+    Some((start, end))
+}
+
+// i" and a", the first pair of quotes around or after the cursor
+fn quote_object(text: &str, index: usize, quote: char, around: bool) -> Option<(usize, usize)> {
+    let mut quotes = Vec::new();
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            quotes.push(i);
+        }
+    }
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|(_, close)| index <= *close)?;
+    let len = quote.len_utf8();
+    if around {
+        let after = close + len;
+        let blanks = text[after..].len() - text[after..].trim_start().len();
+        Some((open, after + blanks))
+    } else {
+        Some((open + len, close))
+    }
+}
+
+// i( and a(. the inside of brackets on lines of their own leaves those lines be
+fn bracket_object(
+    buffer: &Buffer,
+    cursor: Cursor,
+    open: char,
+    close: char,
+    around: bool,
+) -> Option<(Cursor, Cursor)> {
+    let start = if at(buffer, cursor) == open {
+        cursor
+    } else {
+        find_pair(buffer, cursor, open, close, false)?
+    };
+    let end = find_pair(buffer, start, open, close, true)?;
+    if around {
+        return Some((start, Cursor::new(end.line, end.index + close.len_utf8())));
+    }
+
+    let mut inner_start = Cursor::new(start.line, start.index + open.len_utf8());
+    let mut inner_end = end;
+    if inner_start.index == line(buffer, start.line).len() && start.line < end.line {
+        inner_start = Cursor::new(start.line + 1, 0);
+    }
+    if inner_end.line > inner_start.line && line(buffer, end.line)[..end.index].trim().is_empty() {
+        inner_end = Cursor::new(end.line - 1, line(buffer, end.line - 1).len());
+    }
+    if position(&inner_end) < position(&inner_start) {
+        inner_end = inner_start;
+    }
+    Some((inner_start, inner_end))
+}
+
+// the bracket closing the one at the cursor going forward, or the open one
+// around the cursor going back
+fn find_pair(
+    buffer: &Buffer,
+    cursor: Cursor,
+    open: char,
+    close: char,
+    forward: bool,
+) -> Option<Cursor> {
+    let (nest, unnest) = if forward {
+        (open, close)
+    } else {
+        (close, open)
+    };
+    let mut depth = 0;
+    let mut cursor = cursor;
+    loop {
+        cursor = if forward {
+            next(buffer, cursor)?
+        } else {
+            prev(buffer, cursor)?
+        };
+        let c = at(buffer, cursor);
+        if c == nest {
+            depth += 1;
+        } else if c == unnest {
+            if depth == 0 {
+                return Some(cursor);
+            }
+            depth -= 1;
+        }
+    }
+}
+
+// %, from the first bracket at or after the cursor on its line
+fn matching_bracket(buffer: &Buffer, cursor: Cursor) -> Option<Cursor> {
+    let text = line(buffer, cursor.line);
+    let (offset, bracket) = text[cursor.index..]
+        .char_indices()
+        .find(|(_, c)| "()[]{}".contains(*c))?;
+    let at = Cursor::new(cursor.line, cursor.index + offset);
+    match bracket {
+        '(' => find_pair(buffer, at, '(', ')', true),
+        '[' => find_pair(buffer, at, '[', ']', true),
+        '{' => find_pair(buffer, at, '{', '}', true),
+        ')' => find_pair(buffer, at, '(', ')', false),
+        ']' => find_pair(buffer, at, '[', ']', false),
+        _ => find_pair(buffer, at, '{', '}', false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use cosmic_text::{Buffer, Cursor, Edit, FontSystem, Metrics, SyntaxEditor, SyntaxSystem};
+    use iced::advanced::clipboard;
+
+    use super::*;
+
+    static SYNTAX_SYSTEM: LazyLock<SyntaxSystem> = LazyLock::new(SyntaxSystem::new);
+
+    // an editor and vim, keys go to vim first and are typed if it passes them on
+    struct Test {
+        font_system: FontSystem,
+        editor: SyntaxEditor<'static, 'static>,
+        vim: Vim,
+    }
+
+    impl Test {
+        fn new(text: &str) -> Self {
+            let mut font_system = FontSystem::new_with_fonts([]);
+            let buffer = Buffer::new_empty(Metrics::new(14.0, 20.0));
+            let mut editor =
+                SyntaxEditor::new(buffer, &SYNTAX_SYSTEM, "base16-eighties.dark").unwrap();
+            let mut borrowed = editor.borrow_with(&mut font_system);
+            borrowed.insert_string(text, None);
+            borrowed.set_cursor(Cursor::new(0, 0));
+            Self {
+                font_system,
+                editor,
+                vim: Vim::new(),
+            }
+        }
+
+        // escape is \x1b and enter \n
+        fn keys(&mut self, keys: &str) -> Vec<Effect> {
+            let mut editor = self.editor.borrow_with(&mut self.font_system);
+            let mut effects = Vec::new();
+            for c in keys.chars() {
+                let input = match c {
+                    '\x1b' => Input::Escape,
+                    '\n' => Input::Enter,
+                    c => Input::Char(c),
+                };
+                match self.vim.key(input, &mut editor, &mut clipboard::Null) {
+                    Some(taken) => effects.extend(taken),
+                    None => type_input(&mut editor, input),
+                }
+            }
+            effects
+        }
+
+        fn text(&self) -> String {
+            self.editor.with_buffer(|buffer| {
+                let lines: Vec<&str> = buffer.lines.iter().map(|line| line.text()).collect();
+                lines.join("\n")
+            })
+        }
+
+        fn cursor(&self) -> Cursor {
+            self.editor.cursor()
+        }
+    }
+
+    fn run(text: &str, keys: &str) -> String {
+        let mut test = Test::new(text);
+        test.keys(keys);
+        test.text()
+    }
+
+    fn inputs(keys: &str) -> Vec<Input> {
+        keys.chars().map(Input::Char).collect()
+    }
+
+    #[test]
+    fn parses_register_and_counts() {
+        let command = parse(&inputs("\"a2d3w"), false).ok().unwrap();
+        assert_eq!(command.register, Some('a'));
+        assert_eq!(command.count, Some(2));
+        assert!(matches!(
+            command.act,
+            Act::Operate {
+                operator: Operator::Delete,
+                target: Target::Motion(Motion::WordStart { big: false }),
+                count: Some(3),
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_zero_as_motion() {
+        let command = parse(&inputs("0"), false).ok().unwrap();
+        assert_eq!(command.count, None);
+        assert!(matches!(command.act, Act::Move(Motion::LineStart)));
+        let command = parse(&inputs("10l"), false).ok().unwrap();
+        assert_eq!(command.count, Some(10));
+    }
+
+    #[test]
+    fn parses_partial_and_invalid_keys() {
+        assert!(matches!(parse(&inputs("d"), false), Err(Parse::Incomplete)));
+        assert!(matches!(
+            parse(&inputs("\""), false),
+            Err(Parse::Incomplete)
+        ));
+        assert!(matches!(
+            parse(&inputs("di"), false),
+            Err(Parse::Incomplete)
+        ));
+        assert!(matches!(parse(&inputs("dz"), false), Err(Parse::Invalid)));
+        assert!(matches!(parse(&inputs("\"#p"), false), Err(Parse::Invalid)));
+    }
+
+    #[test]
+    fn caps_counts() {
+        let command = parse(&inputs("99999999999999999999999j"), false)
+            .ok()
+            .unwrap();
+        assert_eq!(command.count, Some(MAX_COUNT));
+
+        let mut test = Test::new("one\ntwo");
+        test.keys("99999999999999999999999j");
+        assert_eq!(test.cursor().line, 1);
+        assert_eq!(run("a b c", "9999999999d9999999999w"), "");
+        assert_eq!(run("ab", "99999999999u"), "ab");
+    }
+
+    #[test]
+    fn replaces_count_of_repeat() {
+        let keys = with_count(&inputs("\"a2dw"), Some(3));
+        assert_eq!(keys, inputs("\"a3dw"));
+        assert_eq!(with_count(&inputs("x"), Some(12)), inputs("12x"));
+        assert_eq!(with_count(&inputs("dw"), None), inputs("dw"));
+    }
+
+    #[test]
+    fn change_word_is_change_to_end() {
+        assert_eq!(run("foo bar", "cwxy\x1b"), "xy bar");
+        assert_eq!(run("foo bar", "cexy\x1b"), "xy bar");
+        assert_eq!(run("foo bar", "dw"), "bar");
+        assert_eq!(run("foo bar baz", "c2wx\x1b"), "x baz");
+    }
+
+    #[test]
+    fn delete_word_stops_at_line_end() {
+        assert_eq!(run("foo\nbar", "dw"), "\nbar");
+    }
+
+    #[test]
+    fn text_objects() {
+        assert_eq!(
+            run("say \"hi there\" now", "fhci\"yo\x1b"),
+            "say \"yo\" now"
+        );
+        assert_eq!(run("say \"hi\" now", "fhda\""), "say now");
+        assert_eq!(run("f(a, (b)) x", "fada("), "f x");
+        assert_eq!(run("f(a, (b)) x", "fadi("), "f() x");
+        assert_eq!(run("one two", "wdiw"), "one ");
+        assert_eq!(run("one two", "daw"), "two");
+    }
+
+    #[test]
+    fn word_and_quote_objects() {
+        assert_eq!(word_object("foo bar", 1, false, false), Some((0, 3)));
+        assert_eq!(word_object("foo bar", 1, false, true), Some((0, 4)));
+        assert_eq!(word_object("foo.bar", 4, false, false), Some((4, 7)));
+        assert_eq!(word_object("foo.bar", 4, true, false), Some((0, 7)));
+        assert_eq!(quote_object(r#"a "b\"c" d"#, 0, '"', false), Some((3, 7)));
+        assert_eq!(quote_object(r#"a "b" d"#, 3, '"', true), Some((2, 6)));
+        assert_eq!(quote_object("no quotes", 0, '"', false), None);
+    }
+
+    #[test]
+    fn repeats_changes() {
+        assert_eq!(run("a b c d", "dw."), "c d");
+        assert_eq!(run("abcdef", "x3."), "ef");
+        assert_eq!(run("x", "ia\x1b.."), "aaax");
+        assert_eq!(run("x\ny", "Ab\x1bj."), "xb\nyb");
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(run("one\ntwo", "\"byyj\"bp"), "one\ntwo\none");
+        assert_eq!(run("one two", "\"cyw\"Cyw$\"cp"), "one twoone one ");
+        assert_eq!(run("ab", "\"_x\"_xu"), "");
+    }
+
+    #[test]
+    fn counts_multiply() {
+        assert_eq!(run("a b c d e f g", "2d2w"), "e f g");
+        assert_eq!(run("1\n2\n3\n4", "3dd"), "4");
+        assert_eq!(run("ab", "\"dyl\"d3p"), "aaaab");
+    }
+
+    #[test]
+    fn substitutes() {
+        assert_eq!(run("a a\nb a", ":%s/a/b/g\n"), "b b\nb b");
+        assert_eq!(run("a a\nb a", ":%s/a/b/\n"), "b a\nb b");
+        assert_eq!(run("a a\nb a", ":s/a/c/g\n"), "c c\nb a");
+        assert_eq!(
+            run("foo bar", concat!(r":s/(\w+) (\w+)/\2 \1/", "\n")),
+            "bar foo"
+        );
+
+        let mut test = Test::new("abc");
+        test.keys(":s/x/y/\n");
+        assert_eq!(test.text(), "abc");
+        assert_eq!(test.vim.status().0, "E486: Pattern not found: x");
+    }
+
+    #[test]
+    fn ex_commands() {
+        let mut test = Test::new("one\ntwo");
+        let effects = test.keys(":q!\n");
+        assert!(
+            effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::Publish(Message::TabDiscardCurrent)))
+        );
+        let effects = test.keys(":q\n");
+        assert!(
+            effects
+                .iter()
+                .any(|effect| matches!(effect, Effect::Publish(Message::TabCloseCurrent)))
+        );
+        test.keys(":2\n");
+        assert_eq!(test.cursor().line, 1);
+        test.keys(":nope\n");
+        assert_eq!(test.vim.status().0, "E492: Not an editor command: nope");
+    }
+
+    #[test]
+    fn splits_substitute_parts() {
+        assert_eq!(split_unescaped(r"a\/b/c/g", '/'), ["a/b", "c", "g"]);
+        assert_eq!(split_unescaped(r"a\db#c", '#'), [r"a\db", "c"]);
+        assert_eq!(replacement(r"\1-&$\r"), "${1}-${0}$$\n");
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("%s/a/b/", 3, 9, None), (Some((0, 9)), "s/a/b/"));
+        assert_eq!(parse_range(".,$s", 3, 9, None), (Some((3, 9)), "s"));
+        assert_eq!(parse_range("5,2d", 3, 9, None), (Some((1, 4)), "d"));
+        assert_eq!(
+            parse_range("'<,'>s", 0, 9, Some((2, 4))),
+            (Some((2, 4)), "s")
+        );
+        assert_eq!(parse_range("42", 0, 9, None), (Some((9, 9)), ""));
+        assert_eq!(parse_range("w", 0, 9, None), (None, "w"));
+    }
+}
@@ -1,13 +1,14 @@
 use iced::{Color, daemon::DefaultStyle};
 
-mod button;
-mod container;
+pub mod button;
+mod checkbox;
+pub mod container;
 mod menu;
 mod pane_grid;
 mod pick_list;
 mod scrollable;
 mod tab_bar;
-mod text;
+pub mod text;
 mod text_input;
 
 // iced::Theme requires a default
@@ -18,6 +19,12 @@ pub struct MyTheme {
     background: Color,
     background_light: Color,
     window_background: Color,
+    diff_removed: Color,
+    diff_added: Color,
+    error: Color,
+    text_dimmed: Color,
+    git_modified: Color,
+    git_untracked: Color,
 }
 
 impl Default for MyTheme {
@@ -29,6 +36,12 @@ impl Default for MyTheme {
             background: Color::from_rgb(0.271, 0.271, 0.271),
             background_light: Color::from_rgb(0.35, 0.35, 0.35),
             window_background: Color::from_rgb(0.271, 0.271, 0.271),
+            diff_removed: Color::from_rgb(0.95, 0.45, 0.45),
+            diff_added: Color::from_rgb(0.55, 0.85, 0.5),
+            error: Color::from_rgb(0.55, 0.2, 0.2),
+            text_dimmed: Color::from_rgb(0.6, 0.6, 0.6),
+            git_modified: Color::from_rgb(0.9, 0.75, 0.35),
+            git_untracked: Color::from_rgb(0.45, 0.8, 0.75),
         }
     }
 }
@@ -52,3 +52,14 @@ fn style(status: Status, fg: Color, bg: Color, fg_hover: Color, bg_hover: Color)
          },
     }
 }
+
+// for buttons that stay pressed, like search options
+pub fn toggled(theme: &MyTheme, status: Status) -> Style {
+    style(
+        status,
+        theme.text_inverse,
+        theme.background_accent,
+        theme.text_inverse,
+        theme.background_accent,
+    )
+}
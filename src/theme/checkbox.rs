@@ -0,0 +1,48 @@
+use super::MyTheme;
+use iced::widget::checkbox::{Catalog, Status, Style, StyleFn};
+use iced::{Background, Border, Color, border::Radius};
+
+impl Catalog for MyTheme {
+    type Class<'a> = StyleFn<'a, Self>;
+
+    fn default<'a>() -> Self::Class<'a> {
+        Box::new(primary)
+    }
+
+    fn style(&self, class: &Self::Class<'_>, status: Status) -> Style {
+        class(self, status)
+    }
+}
+
+pub fn primary(theme: &MyTheme, status: Status) -> Style {
+    style(
+        status,
+        theme.text,
+        theme.background_light,
+        theme.text_inverse,
+        theme.background_accent,
+    )
+}
+
+fn style(status: Status, fg: Color, bg: Color, fg_checked: Color, bg_checked: Color) -> Style {
+    let is_checked = match status {
+        Status::Active { is_checked }
+        | Status::Hovered { is_checked }
+        | Status::Disabled { is_checked } => is_checked,
+    };
+    let (icon, background) = if is_checked {
+        (fg_checked, bg_checked)
+    } else {
+        (fg, bg)
+    };
+    Style {
+        background: Background::Color(background),
+        icon_color: icon,
+        border: Border {
+            color: fg,
+            width: 0.0,
+            radius: Radius::default(),
+        },
+        text_color: Some(fg),
+    }
+}
@@ -20,6 +20,28 @@ pub fn primary(theme: &MyTheme) -> Style {
     style(theme.text, theme.background)
 }
 
+// dims what is behind a dialog
+pub fn backdrop(theme: &MyTheme) -> Style {
+    style(theme.text, Color::from_rgba(0.0, 0.0, 0.0, 0.5))
+}
+
+pub fn dialog(theme: &MyTheme) -> Style {
+    style(theme.text, theme.background_light)
+}
+
+pub fn selected(theme: &MyTheme) -> Style {
+    style(theme.text_inverse, theme.background_accent)
+}
+
+// the node of the file being edited
+pub fn active(theme: &MyTheme) -> Style {
+    style(theme.text, theme.background_light)
+}
+
+pub fn error(theme: &MyTheme) -> Style {
+    style(theme.text, theme.error)
+}
+
 fn style(fg: Color, bg: Color) -> Style {
     Style {
         text_color: Some(fg),
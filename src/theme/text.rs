@@ -21,6 +21,31 @@ pub fn none(theme: &MyTheme) -> Style {
     style(None)
 }
 
+pub fn removed(theme: &MyTheme) -> Style {
+    style(Some(theme.diff_removed))
+}
+
+pub fn added(theme: &MyTheme) -> Style {
+    style(Some(theme.diff_added))
+}
+
+pub fn dimmed(theme: &MyTheme) -> Style {
+    style(Some(theme.text_dimmed))
+}
+
+pub fn modified(theme: &MyTheme) -> Style {
+    style(Some(theme.git_modified))
+}
+
+pub fn untracked(theme: &MyTheme) -> Style {
+    style(Some(theme.git_untracked))
+}
+
+// conflicts take the color of removed lines
+pub fn conflicted(theme: &MyTheme) -> Style {
+    style(Some(theme.diff_removed))
+}
+
 fn style(fg: Option<Color>) -> Style {
     Style { color: fg }
 }
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::{
+    Subscription,
+    futures::{SinkExt, StreamExt, channel::mpsc},
+    stream,
+};
+use notify::{EventKind, RecursiveMode, Watcher};
+use notify_debouncer_mini::new_debouncer;
+
+use crate::Message;
+
+// a burst of writes, like a build, reaches the tree as one batch per period
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// changes made by other programs to the given files. the parent directories are
+// watched, a save that renames over a file would drop a watch on the file itself
+pub fn files(paths: Vec<PathBuf>) -> Subscription<Message> {
+    if paths.is_empty() {
+        return Subscription::none();
+    }
+
+    // a new set of open files restarts the watcher
+    Subscription::run_with_id(
+        paths.clone(),
+        stream::channel(100, move |mut output| async move {
+            let (sender, mut receiver) = mpsc::unbounded();
+            let watcher = notify::recommended_watcher(move |event| {
+                let _ = sender.unbounded_send(event);
+            });
+            let mut watcher = match watcher {
+                Ok(watcher) => watcher,
+                Err(err) => {
+                    log::error!("could not watch files: {}", err);
+                    return;
+                }
+            };
+
+            let dirs: HashSet<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+            for dir in dirs {
+                if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("could not watch {}: {}", dir.display(), err);
+                }
+            }
+
+            while let Some(event) = receiver.next().await {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        log::warn!("file watcher: {}", err);
+                        continue;
+                    }
+                };
+                if matches!(event.kind, EventKind::Access(_)) {
+                    continue;
+                }
+                for path in event.paths {
+                    if paths.contains(&path) {
+                        let _ = output.send(Message::FileChanged(path)).await;
+                    }
+                }
+            }
+        }),
+    )
+}
+
+// paths changed in the given directories, batched. only the directories themselves
+// are watched, not all below them: trees like target or node_modules would take
+// a watch per directory and flood the batches on every build
+pub fn directories(dirs: Vec<PathBuf>) -> Subscription<Message> {
+    if dirs.is_empty() {
+        return Subscription::none();
+    }
+
+    // a new set of directories restarts the watcher
+    Subscription::run_with_id(
+        dirs.clone(),
+        stream::channel(100, move |mut output| async move {
+            let (sender, mut receiver) = mpsc::unbounded();
+            let debouncer = new_debouncer(DEBOUNCE, move |events| {
+                let _ = sender.unbounded_send(events);
+            });
+            let mut debouncer = match debouncer {
+                Ok(debouncer) => debouncer,
+                Err(err) => {
+                    log::error!("could not watch directories: {}", err);
+                    return;
+                }
+            };
+            for dir in &dirs {
+                if let Err(err) = debouncer.watcher().watch(dir, RecursiveMode::NonRecursive) {
+                    log::warn!("could not watch {}: {}", dir.display(), err);
+                }
+            }
+
+            while let Some(events) = receiver.next().await {
+                let events: Vec<notify_debouncer_mini::DebouncedEvent> = match events {
+                    Ok(events) => events,
+                    Err(err) => {
+                        log::warn!("directory watcher: {}", err);
+                        continue;
+                    }
+                };
+                let paths: HashSet<PathBuf> = events.into_iter().map(|event| event.path).collect();
+                let _ = output
+                    .send(Message::ProjectTreeChanged(paths.into_iter().collect()))
+                    .await;
+            }
+        }),
+    )
+}